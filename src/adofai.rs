@@ -0,0 +1,387 @@
+use std::io::{Cursor, Read};
+
+use serde_json::Value;
+
+/// What `/analyze` reports about a chart.
+#[derive(Debug, Clone)]
+pub struct ChartInfo {
+    pub song: String,
+    pub artist: String,
+    pub author: String,
+    pub tile_count: u32,
+    pub min_bpm: f64,
+    pub max_bpm: f64,
+    pub speed_changes: u32,
+    pub twirls: u32,
+    pub holds: u32,
+    pub duration_secs: f64,
+}
+
+type ChartResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Analyzes an uploaded file, either a bare `.adofai` chart or a zip package containing one.
+pub fn analyze_file(file_name: &str, bytes: &[u8]) -> ChartResult<ChartInfo> {
+    let file_name = file_name.to_lowercase();
+    if file_name.ends_with(".zip") {
+        analyze_package(bytes)
+    } else if file_name.ends_with(".adofai") {
+        analyze_chart(bytes)
+    } else {
+        Err("Only .adofai charts and .zip packages can be analyzed".into())
+    }
+}
+
+/// Biggest chart read out of a package. Packages are untrusted uploads, so a tiny zip
+/// can't be allowed to inflate into gigabytes.
+const MAX_CHART_SIZE: u64 = 32 * 1024 * 1024;
+
+pub fn analyze_package(bytes: &[u8]) -> ChartResult<ChartInfo> {
+    analyze_chart(&extract_chart(bytes, MAX_CHART_SIZE)?)
+}
+
+/// Picks the chart out of a level package, skipping the editor's backup copies.
+fn extract_chart(bytes: &[u8], max_size: u64) -> ChartResult<Vec<u8>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+
+    let mut chart_index = None;
+    let mut chart_size = 0;
+    for index in 0..archive.len() {
+        let file = archive.by_index(index)?;
+        let name = file.name().to_lowercase();
+        if !name.ends_with(".adofai") || name.contains("backup") {
+            continue;
+        }
+        // Packages sometimes ship extra charts, the biggest one is the actual level.
+        if chart_index.is_none() || file.size() > chart_size {
+            chart_index = Some(index);
+            chart_size = file.size();
+        }
+    }
+
+    let index = chart_index.ok_or("No .adofai chart found in the package")?;
+    let too_big = || format!("The chart is over {} MB", max_size / 1024 / 1024);
+    if chart_size > max_size {
+        return Err(too_big().into());
+    }
+
+    // The size in the zip header is only a claim, the read is capped too.
+    let mut chart = Vec::new();
+    archive
+        .by_index(index)?
+        .take(max_size + 1)
+        .read_to_end(&mut chart)?;
+    if chart.len() as u64 > max_size {
+        return Err(too_big().into());
+    }
+
+    Ok(chart)
+}
+
+pub fn analyze_chart(bytes: &[u8]) -> ChartResult<ChartInfo> {
+    let text = String::from_utf8_lossy(bytes);
+    let json: Value = serde_json::from_str(&sanitize_json(&text))
+        .map_err(|e| format!("Couldn't read the chart: {}", e))?;
+
+    let angles = match json["angleData"].as_array() {
+        Some(angles) => angles
+            .iter()
+            .map(|angle| angle.as_f64().unwrap_or(0.))
+            .collect::<Vec<_>>(),
+        None => json["pathData"]
+            .as_str()
+            .ok_or("The chart has neither angleData nor pathData")?
+            .chars()
+            .map(path_angle)
+            .collect(),
+    };
+
+    let settings = &json["settings"];
+    let pitch = settings["pitch"].as_f64().unwrap_or(100.) / 100.;
+    let mut bpm = settings["bpm"].as_f64().unwrap_or(100.);
+    let (mut min_bpm, mut max_bpm) = (bpm, bpm);
+
+    let mut events = json["actions"]
+        .as_array()
+        .map(|actions| actions.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|action| Some((action["floor"].as_u64()? as usize, action)))
+        .collect::<Vec<_>>();
+    events.sort_by_key(|(floor, _)| *floor);
+
+    let mut speed_changes = 0;
+    let mut twirls = 0;
+    let mut holds = 0;
+    let mut twirled = false;
+    let mut beats_to_seconds = 0.;
+    let mut direction = 0.;
+    let mut next_event = 0;
+
+    for (floor, angle) in angles.iter().enumerate() {
+        let mut extra_beats = 0.;
+
+        while let Some((event_floor, action)) = events.get(next_event) {
+            if *event_floor > floor {
+                break;
+            }
+            next_event += 1;
+
+            match action["eventType"].as_str().unwrap_or("") {
+                "SetSpeed" => {
+                    speed_changes += 1;
+                    bpm = match action["speedType"].as_str() {
+                        Some("Multiplier") => bpm * action["bpmMultiplier"].as_f64().unwrap_or(1.),
+                        _ => action["beatsPerMinute"].as_f64().unwrap_or(bpm),
+                    };
+                    min_bpm = min_bpm.min(bpm);
+                    max_bpm = max_bpm.max(bpm);
+                }
+                "Twirl" => {
+                    twirls += 1;
+                    twirled = !twirled;
+                }
+                "Hold" => {
+                    holds += 1;
+                    // Every hold repetition is one more full turn around the tile.
+                    extra_beats += action["duration"].as_f64().unwrap_or(0.) * 2.;
+                }
+                "Pause" => extra_beats += action["duration"].as_f64().unwrap_or(0.),
+                _ => {}
+            }
+        }
+
+        // Midspins take no time and flip the direction the planet comes from.
+        if *angle == MIDSPIN {
+            direction = (direction + 180.) % 360.;
+            continue;
+        }
+
+        let mut turn = (direction + 180. - angle).rem_euclid(360.);
+        if twirled {
+            turn = 360. - turn;
+        }
+        if turn < 1e-6 {
+            turn = 360.;
+        }
+
+        let beats = turn / 180. + extra_beats;
+        if bpm > 0. {
+            beats_to_seconds += beats * 60. / (bpm * pitch);
+        }
+        direction = *angle;
+    }
+
+    Ok(ChartInfo {
+        song: strip_tags(settings["song"].as_str().unwrap_or("Unknown")),
+        artist: strip_tags(settings["artist"].as_str().unwrap_or("Unknown")),
+        author: strip_tags(settings["author"].as_str().unwrap_or("Unknown")),
+        // Midspins aren't hit, they only turn the planet around.
+        tile_count: angles.iter().filter(|angle| **angle != MIDSPIN).count() as u32,
+        min_bpm: min_bpm * pitch,
+        max_bpm: max_bpm * pitch,
+        speed_changes,
+        twirls,
+        holds,
+        duration_secs: beats_to_seconds,
+    })
+}
+
+const MIDSPIN: f64 = 999.;
+
+/// Angles of the letters old charts use in `pathData`.
+fn path_angle(c: char) -> f64 {
+    match c {
+        'R' => 0.,
+        'p' => 15.,
+        'J' => 30.,
+        'E' => 45.,
+        'T' => 60.,
+        'o' => 75.,
+        'U' => 90.,
+        'q' => 105.,
+        'G' => 120.,
+        'Q' => 135.,
+        'H' => 150.,
+        'W' => 165.,
+        'L' => 180.,
+        'x' => 195.,
+        'N' => 210.,
+        'Z' => 225.,
+        'F' => 240.,
+        'V' => 255.,
+        'D' => 270.,
+        'Y' => 285.,
+        'B' => 300.,
+        'C' => 315.,
+        'M' => 330.,
+        'A' => 345.,
+        '!' => MIDSPIN,
+        _ => 0.,
+    }
+}
+
+/// Level names can contain Unity rich text like `<color=#fff>`.
+fn strip_tags(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => stripped.push(c),
+            _ => {}
+        }
+    }
+    stripped
+}
+
+/// ADOFAI writes almost-JSON: a BOM, trailing commas and sometimes no comma between objects.
+fn sanitize_json(text: &str) -> String {
+    let text = text.trim_start_matches('\u{feff}');
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut out = String::with_capacity(text.len());
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (index, c) in chars.iter().copied().enumerate() {
+        if in_string {
+            out.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        let next = chars[index + 1..]
+            .iter()
+            .copied()
+            .find(|next| !next.is_whitespace());
+
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            ',' if matches!(next, Some('}') | Some(']') | None) => {}
+            '}' | ']' if matches!(next, Some('{') | Some('"')) => {
+                out.push(c);
+                out.push(',');
+            }
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{MIDSPIN, analyze_chart, extract_chart, path_angle, sanitize_json};
+
+    fn package(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer
+                .start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn sanitizes_almost_json() {
+        assert_eq!(
+            sanitize_json("\u{feff}{\"a\": [1, 2,], \"b\": {\"c\": 1,},}"),
+            "{\"a\": [1, 2], \"b\": {\"c\": 1}}"
+        );
+        assert_eq!(
+            sanitize_json("[{\"a\": 1}\n{\"b\": \"x,]\"}]"),
+            "[{\"a\": 1},\n{\"b\": \"x,]\"}]"
+        );
+    }
+
+    #[test]
+    fn path_letters() {
+        assert_eq!(path_angle('R'), 0.);
+        assert_eq!(path_angle('U'), 90.);
+        assert_eq!(path_angle('L'), 180.);
+        assert_eq!(path_angle('!'), MIDSPIN);
+    }
+
+    #[test]
+    fn straight_path_chart() {
+        let chart = analyze_chart(
+            br#"{"pathData": "RRRR", "settings": {"bpm": 120, "song": "<b>Song</b>",}, "actions": [],}"#,
+        )
+        .unwrap();
+
+        assert_eq!(chart.tile_count, 4);
+        assert_eq!(chart.song, "Song");
+        // Every straight tile is half a turn, one beat at 120 BPM.
+        assert!((chart.duration_secs - 2.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn midspins_are_not_tiles() {
+        let chart = analyze_chart(
+            format!(
+                r#"{{"angleData": [0, {MIDSPIN}, 0, 90], "settings": {{"bpm": 60}}, "actions": []}}"#
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(chart.tile_count, 3);
+    }
+
+    #[test]
+    fn counts_events() {
+        let chart = analyze_chart(
+            br#"{
+                "angleData": [0, 0, 0, 0],
+                "settings": {"bpm": 100},
+                "actions": [
+                    {"floor": 1, "eventType": "SetSpeed", "speedType": "Bpm", "beatsPerMinute": 200}
+                    {"floor": 2, "eventType": "Twirl"}
+                    {"floor": 3, "eventType": "SetSpeed", "speedType": "Multiplier", "bpmMultiplier": 0.25}
+                    {"floor": 3, "eventType": "Hold", "duration": 1}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(chart.speed_changes, 2);
+        assert_eq!(chart.twirls, 1);
+        assert_eq!(chart.holds, 1);
+        assert_eq!(chart.min_bpm, 50.);
+        assert_eq!(chart.max_bpm, 200.);
+    }
+
+    #[test]
+    fn package_picks_the_biggest_chart() {
+        let bytes = package(&[
+            ("level/backup_1.adofai", "{\"pathData\": \"RRRRRRRR\"}"),
+            ("level/level.adofai", "{\"pathData\": \"RR\"}"),
+            ("level/song.ogg", "not a chart"),
+        ]);
+
+        let chart = extract_chart(&bytes, 1024).unwrap();
+        assert_eq!(chart, b"{\"pathData\": \"RR\"}");
+    }
+
+    #[test]
+    fn package_rejects_oversized_charts() {
+        let bytes = package(&[("level.adofai", &"R".repeat(2048))]);
+
+        assert!(extract_chart(&bytes, 1024).is_err());
+        assert!(extract_chart(&bytes, 4096).is_ok());
+        assert!(extract_chart(&package(&[("song.ogg", "")]), 1024).is_err());
+    }
+}
@@ -56,6 +56,7 @@ impl CacheManager {
         self.cache.get(key)?.value.downcast_ref::<T>()
     }
 
+    #[allow(dead_code)]
     pub fn get_owned<T: 'static + Copy>(&mut self, key: &str) -> Option<T> {
         let expired = if let Some(cache) = self.cache.get(key) {
             if let Some(expire) = cache.expire {
@@ -81,18 +82,14 @@ impl CacheManager {
     pub fn cleanup_expired(&mut self) {
         let now = Instant::now();
         self.cache
-            .retain(|_, entry| entry.expire.map_or(true, |exp| now < exp));
+            .retain(|_, entry| entry.expire.is_none_or(|exp| now < exp));
     }
 
     pub fn get_all_entries<T: 'static>(&self) -> HashMap<String, &T> {
         self.cache
             .iter()
             .filter_map(|(key, entry)| {
-                if let Some(value) = entry.value.downcast_ref::<T>() {
-                    Some((key.clone(), value))
-                } else {
-                    None
-                }
+                entry.value.downcast_ref::<T>().map(|value| (key.clone(), value))
             })
             .collect()
     }
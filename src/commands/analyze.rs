@@ -0,0 +1,187 @@
+use std::sync::Arc;
+
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    CreateEmbed, CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage,
+    EditInteractionResponse,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    LogLevel,
+    adofai::{ChartInfo, analyze_file},
+    cache_manager::{CacheManager, LiveTime},
+    log_message,
+};
+
+/// Discord allows bigger uploads, but anything past this isn't a level package.
+const MAX_FILE_SIZE: u32 = 50 * 1024 * 1024;
+
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) -> Result<(), serenity::Error> {
+    let start_time = std::time::Instant::now();
+
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Defer(
+                CreateInteractionResponseMessage::new().content("Analyzing the chart..."),
+            ),
+        )
+        .await?;
+
+    let attachment = interaction
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == "chart")
+        .and_then(|option| option.value.as_attachment_id())
+        .and_then(|id| interaction.data.resolved.attachments.get(&id));
+
+    let level_id = interaction
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == "level_id")
+        .and_then(|option| option.value.as_i64())
+        .map(|id| id as u32);
+
+    let Some(attachment) = attachment else {
+        interaction
+            .edit_response(
+                ctx,
+                EditInteractionResponse::new().content("⚠️ Attach a .adofai chart or .zip package"),
+            )
+            .await?;
+        return Ok(());
+    };
+
+    if attachment.size > MAX_FILE_SIZE {
+        interaction
+            .edit_response(
+                ctx,
+                EditInteractionResponse::new().content("⚠️ The file is too big to analyze"),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let chart = match download(&attachment.url).await {
+        Ok(bytes) => analyze_file(&attachment.filename, &bytes),
+        Err(e) => Err(e),
+    };
+
+    let chart = match chart {
+        Ok(chart) => chart,
+        Err(e) => {
+            log_message(
+                format!("Couldn't analyze {}: {e}", attachment.filename).as_str(),
+                LogLevel::Warning,
+            );
+            interaction
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new().content(format!("⚠️ {}", e)),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if let Some(level_id) = level_id {
+        cache_manager.lock().await.add(
+            format!("chart_{level_id}"),
+            chart.clone(),
+            Some(LiveTime::Hours(24)),
+        );
+    }
+
+    let stop_time = std::time::Instant::now();
+    let elapsed_time = stop_time.duration_since(start_time);
+
+    let embed = chart_embed(&chart, level_id).footer(CreateEmbedFooter::new(format!(
+        "Analyzed in {} ms",
+        elapsed_time.as_millis()
+    )));
+
+    interaction
+        .edit_response(ctx, EditInteractionResponse::new().embed(embed))
+        .await?;
+
+    Ok(())
+}
+
+async fn download(url: &str) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let response = reqwest::get(url).await?;
+
+    if !response.status().is_success() {
+        return Err(format!("Couldn't download the file: {}", response.status()).into());
+    }
+
+    Ok(response.bytes().await?.to_vec())
+}
+
+fn chart_embed(chart: &ChartInfo, level_id: Option<u32>) -> CreateEmbed {
+    let duration = chart.duration_secs.round() as u64;
+
+    CreateEmbed::new()
+        .title(format!("{} - {}", chart.artist, chart.song))
+        .description(format!("*charted by {}*", chart.author))
+        .field("**Tiles**", chart.tile_count.to_string(), true)
+        .field(
+            "**BPM**",
+            if (chart.max_bpm - chart.min_bpm).abs() < 0.01 {
+                format!("{:.2}", chart.min_bpm)
+            } else {
+                format!("{:.2} - {:.2}", chart.min_bpm, chart.max_bpm)
+            },
+            true,
+        )
+        .field(
+            "**Duration**",
+            format!("~{}:{:02}", duration / 60, duration % 60),
+            true,
+        )
+        .field("**Speed changes**", chart.speed_changes.to_string(), true)
+        .field("**Twirls**", chart.twirls.to_string(), true)
+        .field("**Holds**", chart.holds.to_string(), true)
+        .field(
+            "",
+            match level_id {
+                Some(id) => format!(
+                    "-# The tile count will be used by `/calcscore` for level {} for the next 24 hours",
+                    id
+                ),
+                None => {
+                    "-# Pass `level_id` to let `/calcscore` use this tile count".to_string()
+                }
+            },
+            false,
+        )
+        .color(0xFF69B4)
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("analyze")
+        .description("Analyze a .adofai chart or level package")
+        .dm_permission(true)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Attachment,
+                "chart",
+                "The .adofai chart or the .zip package",
+            )
+            .required(true),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "level_id",
+                "The TUF level this chart belongs to",
+            )
+            .required(false),
+        )
+}
@@ -0,0 +1,194 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, LazyLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use serenity::all::{
+    AutocompleteChoice, CommandInteraction, Context, CreateAutocompleteResponse,
+    CreateInteractionResponse, UserId,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    LogLevel,
+    cache_manager::{CacheManager, LiveTime},
+    log_message,
+    tuforums::{
+        clear_info::search_clears,
+        leaderboard::search_players,
+        level::{LevelFilter, search_levels},
+    },
+};
+
+/// How long a user has to stop typing before we hit the API.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Discord shows at most 25 suggestions.
+const MAX_CHOICES: u32 = 25;
+
+/// The latest pending autocomplete request of every user. Older requests still waiting out the
+/// debounce see a newer generation and give up without calling the API.
+/// Entries are removed once the latest request is answered.
+static GENERATIONS: LazyLock<std::sync::Mutex<HashMap<UserId, u64>>> =
+    LazyLock::new(|| std::sync::Mutex::new(HashMap::new()));
+
+/// Generations are global so a user's entry can be dropped without ids being reused.
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Answers autocomplete for every ID option the bot has, based on the option's name.
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) -> Result<(), serenity::Error> {
+    let Some(focused) = interaction.data.autocomplete() else {
+        return Ok(());
+    };
+
+    let kind = match (interaction.data.name.as_str(), focused.name) {
+        (_, "level_id") | ("level", "id") => "level",
+        (_, "player_id" | "profile_id") | ("leaderboard", "around") | ("compare", "a" | "b") => {
+            "player"
+        }
+        (_, "clear_id") => "clear",
+        _ => return Ok(()),
+    };
+    let query = focused.value.trim().to_lowercase();
+
+    let choices = if query.is_empty() {
+        Vec::new()
+    } else {
+        let key = format!("autocomplete_{kind}_{query}");
+        let cached = cache_manager
+            .lock()
+            .await
+            .get::<Vec<AutocompleteChoice>>(&key)
+            .cloned();
+
+        match cached {
+            Some(choices) => choices,
+            None => match debounce(interaction.user.id).await {
+                // Superseded by a newer request, Discord still expects an answer.
+                None => Vec::new(),
+                Some(generation) => {
+                    let choices = search_cached(kind, &query, key, cache_manager).await;
+                    finish(interaction.user.id, generation);
+                    choices
+                }
+            },
+        }
+    };
+
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Autocomplete(
+                CreateAutocompleteResponse::new().set_choices(choices),
+            ),
+        )
+        .await
+}
+
+/// Searches and caches the result for a few minutes, failures are logged and give no choices.
+async fn search_cached(
+    kind: &str,
+    query: &str,
+    key: String,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) -> Vec<AutocompleteChoice> {
+    match search(kind, query).await {
+        Ok(choices) => {
+            cache_manager
+                .lock()
+                .await
+                .add(key, choices.clone(), Some(LiveTime::Minutes(5)));
+            choices
+        }
+        Err(e) => {
+            log_message(
+                format!("Autocomplete search for {kind} failed: {e}").as_str(),
+                LogLevel::Warning,
+            );
+            Vec::new()
+        }
+    }
+}
+
+/// Waits out the debounce and returns the request's generation if it's still the user's latest.
+async fn debounce(user_id: UserId) -> Option<u64> {
+    let generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+    GENERATIONS.lock().unwrap().insert(user_id, generation);
+
+    tokio::time::sleep(DEBOUNCE).await;
+
+    (GENERATIONS.lock().unwrap().get(&user_id) == Some(&generation)).then_some(generation)
+}
+
+/// Forgets the user once their latest request is answered, unless a newer one came in meanwhile.
+fn finish(user_id: UserId, generation: u64) {
+    let mut generations = GENERATIONS.lock().unwrap();
+    if generations.get(&user_id) == Some(&generation) {
+        generations.remove(&user_id);
+    }
+}
+
+async fn search(
+    kind: &str,
+    query: &str,
+) -> Result<Vec<AutocompleteChoice>, Box<dyn std::error::Error + Send + Sync>> {
+    let choices = match kind {
+        "level" => search_levels(&LevelFilter::query(query), 0, MAX_CHOICES)
+            .await?
+            .levels
+            .into_iter()
+            .map(|level| {
+                choice(
+                    format!(
+                        "#{} | {} - {} ({})",
+                        level.id, level.artist, level.title, level.difficulty
+                    ),
+                    level.id as u64,
+                )
+            })
+            .collect(),
+        "player" => search_players(query, MAX_CHOICES)
+            .await?
+            .into_iter()
+            .map(|player| {
+                let rank = match player.rank {
+                    0 => "unranked".to_string(),
+                    rank => format!("#{}", rank),
+                };
+                choice(
+                    format!("{} | {} [{}]", player.name, rank, player.id),
+                    player.id,
+                )
+            })
+            .collect(),
+        "clear" => search_clears(query, MAX_CHOICES)
+            .await?
+            .into_iter()
+            .map(|clear| {
+                choice(
+                    format!(
+                        "#{} | {} - {} - {}",
+                        clear.id, clear.player_name, clear.artist, clear.title
+                    ),
+                    clear.id,
+                )
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    Ok(choices)
+}
+
+/// Choice names are capped at 100 characters by Discord.
+fn choice(name: String, id: u64) -> AutocompleteChoice {
+    AutocompleteChoice::new(name.chars().take(100).collect::<String>(), id)
+}
@@ -3,7 +3,7 @@ use serenity::all::{
     CreateInteractionResponseMessage, EditInteractionResponse,
 };
 
-use crate::{formulas, tuforums::clear_info::Judgements, utils::get_option_as_string};
+use crate::{formulas, judgement_parser::parse_judgements, utils::get_option_as_string};
 
 pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
     let start_time = std::time::Instant::now();
@@ -18,31 +18,23 @@ pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(),
         .await
         .expect("Something went wrong while creating response");
 
-    let judgements = get_option_as_string(interaction, "judgements", "0 0 0 0 0 0 0")
-        .split(' ')
-        .map(|s| s.parse::<u64>().unwrap_or(0))
-        .collect::<Vec<u64>>();
+    let input = get_option_as_string(interaction, "judgements", "");
 
-    if judgements.len() != 7 {
-        interaction
-            .edit_response(
-                ctx,
-                EditInteractionResponse::new().content("Invalid judgements"),
-            )
-            .await
-            .expect("Something went wrong while editing response");
-        return Ok(());
-    }
+    let judgements = match parse_judgements(&input) {
+        Ok(judgements) => judgements,
+        Err(e) => {
+            interaction
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new().content(e.render(&input)),
+                )
+                .await
+                .expect("Something went wrong while editing response");
+            return Ok(());
+        }
+    };
 
-    let accuracy = formulas::acc_by_judgement(Judgements(
-        judgements[0],
-        judgements[1],
-        judgements[2],
-        judgements[3],
-        judgements[4],
-        judgements[5],
-        judgements[6],
-    )) * 100.0;
+    let accuracy = formulas::acc_by_judgement(judgements) * 100.0;
 
     let stop_time = std::time::Instant::now();
     let elapsed_time = stop_time.duration_since(start_time);
@@ -52,13 +44,13 @@ pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(),
         .field("Using those judgements", format!("
         ```ansi
 [2;31m{}[0m [2;33m{}[0m [2;32m{}[0m [1;32m{}[0m [2;32m{}[0m [2;33m{}[0m [2;31m{}[0m```", 
-        judgements[0],
-        judgements[1],
-        judgements[2],
-        judgements[3],
-        judgements[4],
-        judgements[5],
-        judgements[6],
+        judgements.0,
+        judgements.1,
+        judgements.2,
+        judgements.3,
+        judgements.4,
+        judgements.5,
+        judgements.6,
         ),
         false)
         .field("**Your Accuracy is**", format!("{:.2}%", accuracy), false)
@@ -83,7 +75,7 @@ pub fn register() -> CreateCommand {
             serenity::all::CreateCommandOption::new(
                 serenity::all::CommandOptionType::String,
                 "judgements",
-                "The judgements to calculate the accuracy from",
+                "Judgement counts, e.g. 0 3 12 1200 10 2 0, perfect=1200 early=3 or the results text",
             )
             .required(true),
         )
@@ -1,13 +1,41 @@
-use std::vec;
+use std::{collections::HashMap, sync::Arc, vec};
 
 use serenity::all::{
-    Color, CommandInteraction, Context, CreateCommand, CreateCommandOption, CreateEmbed,
-    CreateEmbedFooter, CreateInteractionResponseMessage, EditInteractionResponse,
+    CommandDataOption, CommandInteraction, CommandOptionType, Context, CreateCommand,
+    CreateCommandOption, CreateEmbedFooter, CreateInteractionResponseMessage,
+    EditInteractionResponse, UserId,
 };
 
-use crate::{formulas::score_final, tuforums::level::get_level};
+use tokio::sync::Mutex;
 
-pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
+use crate::{
+    adofai::ChartInfo,
+    cache_manager::CacheManager,
+    commands::{
+        calculator::{
+            CalculatorState, MAX_SPEED, MIN_SPEED, TileCountSource, calculator_buttons,
+            calculator_embed, round_2,
+        },
+        profile::get_profile_linked,
+    },
+    database::Database,
+    formulas::{
+        ranked_score, ranked_weight,
+        scoring_model::{MODELS, ScoringModel, default_model, model_by_id},
+    },
+    tuforums::{
+        leaderboard::estimate_rank,
+        level::get_level,
+        profile::{Pass, get_passes, get_profile},
+    },
+};
+
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &Database,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) -> Result<(), serenity::Error> {
     let start_time = std::time::Instant::now();
 
     interaction
@@ -34,6 +62,7 @@ pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(),
         .iter()
         .find(|option| option.name == "x_acc")
         .and_then(|option| option.value.as_f64())
+        .map(round_2)
         .unwrap_or(0.);
 
     let misses = interaction
@@ -50,7 +79,7 @@ pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(),
         .iter()
         .find(|option| option.name == "tile_count")
         .and_then(|option| option.value.as_i64())
-        .unwrap_or(0) as u32;
+        .map(|tile_count| tile_count as u32);
 
     let speed = interaction
         .data
@@ -58,6 +87,7 @@ pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(),
         .iter()
         .find(|option| option.name == "speed")
         .and_then(|option| option.value.as_f64())
+        .map(round_2)
         .unwrap_or(1.);
 
     let ranked_position = interaction
@@ -66,7 +96,7 @@ pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(),
         .iter()
         .find(|option| option.name == "ranked_position")
         .and_then(|option| option.value.as_i64())
-        .unwrap_or(1) as f64;
+        .unwrap_or(1) as u32;
 
     let level = match get_level(id).await {
         Ok(map) => map,
@@ -91,43 +121,219 @@ pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(),
         level.score_base
     };
 
-    let score = score_final(base_score, x_acc, tile_count, misses, speed);
-    let ranked_score = if ranked_position >= 20. {
-        0.0
-    } else {
-        score * 0.9f64.powf(ranked_position - 1.)
+    let chart_tile_count = cache_manager
+        .lock()
+        .await
+        .get::<ChartInfo>(format!("chart_{id}").as_str())
+        .map(|chart| chart.tile_count);
+
+    let (tile_count, tile_count_source) = match (tile_count, level.tile_count, chart_tile_count) {
+        (Some(tile_count), _, _) => (tile_count, TileCountSource::Given),
+        (None, Some(tile_count), _) => (tile_count, TileCountSource::Tuf),
+        (None, None, Some(tile_count)) => (tile_count, TileCountSource::Analyze),
+        (None, None, None) => {
+            interaction
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new().content(
+                        "⚠️ The tile count of this level is unknown.\nPass `tile_count` or upload the chart with `/analyze` and this `level_id`.",
+                    ),
+                )
+                .await
+                .expect("Failed to edit the response");
+            return Ok(());
+        }
+    };
+
+    let state = CalculatorState {
+        level_id: level.id,
+        x_acc,
+        tile_count,
+        tile_count_source,
+        misses,
+        speed,
+        ranked_position,
+        model: selected_model(&interaction.data.options),
     };
+    let score = state.model.score(base_score, x_acc, tile_count, misses, speed);
+
+    let compare = interaction
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == "compare")
+        .and_then(|option| option.value.as_bool())
+        .unwrap_or(false);
+
+    let ranked_field = ranked_field(
+        interaction.user.id,
+        level.id,
+        score,
+        ranked_position,
+        database,
+        cache_manager,
+    )
+    .await;
 
     let stop_time = std::time::Instant::now();
     let elapsed_time = stop_time.duration_since(start_time);
 
-    let embed = CreateEmbed::new()
-    .title(format!(
-        "{} - {} | ID: {}",
-        level.artist, level.title, level.id
-    ))
-    .description(format!("*charted by {}*", level.creator))
-    .field("Using those informations", format!("``base score: {}`` | ``xAcc: {}``\n``tile Count: {}`` | ``misses: {}`` | ``speed: {}x``", base_score, x_acc, tile_count, misses, speed), false)
-    .thumbnail(level.difficulty.icon.to_string())
-    .field("Your score", format!("**{:.2}**", score), true)
-    .field("Your ranked score", format!("**{:.2} (#{})**", ranked_score, ranked_position), true)
-    .color(Color::from_rgb(level.difficulty.color.0, level.difficulty.color.1, level.difficulty.color.2))
-    .footer(CreateEmbedFooter::new(format!("Response time {} ms", elapsed_time.as_millis().to_string())));
+    let embed = calculator_embed(&level, &state, ranked_field)
+    .fields(compare.then(|| ("Under every model", models_field(base_score, x_acc, tile_count, misses, speed), false)))
+    .footer(CreateEmbedFooter::new(format!("Response time {} ms", elapsed_time.as_millis())));
 
     interaction
-        .edit_response(ctx, EditInteractionResponse::new().add_embed(embed))
+        .edit_response(
+            ctx,
+            EditInteractionResponse::new()
+                .add_embed(embed)
+                .components(calculator_buttons(&state)),
+        )
         .await
         .expect("Failed to edit the response");
 
     Ok(())
 }
 
+/// The ranked score field, simulated against the user's passes when their profile is linked.
+pub async fn ranked_field(
+    user_id: UserId,
+    level_id: u32,
+    score: f64,
+    ranked_position: u32,
+    database: &Database,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) -> String {
+    let linked_profile = get_profile_linked(user_id.get() as i64, database)
+        .await
+        .ok();
+
+    match linked_profile {
+        Some(profile_id) => simulate_ranked(profile_id, level_id, score, cache_manager).await,
+        None => None,
+    }
+    .unwrap_or_else(|| {
+        let ranked_score = score * ranked_weight(ranked_position);
+        format!(
+            "**{:.2} (#{})**\n-# link your profile with `/link` to simulate it against your passes",
+            ranked_score, ranked_position
+        )
+    })
+}
+
+/// Inserts the hypothetical pass into the player's real passes and reports the ranked score change.
+async fn simulate_ranked(
+    profile_id: u64,
+    level_id: u32,
+    score: f64,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) -> Option<String> {
+    let passes = get_passes(profile_id, Some(cache_manager)).await.ok()?;
+    let (profile, _) = get_profile(profile_id, Some(cache_manager)).await.ok()?;
+
+    let (current, new, position) = ranked_impact(&passes, level_id, score);
+    let delta = new - current;
+    let total = profile.stats.ranked_score + delta;
+
+    let rank = profile.stats.rank.0.max(0) as u64;
+    let new_rank = if delta > 0. {
+        estimate_rank(total, rank).await.ok()
+    } else {
+        Some(rank)
+    };
+
+    Some(format!(
+        "**{:.2}** (+{:.2})\n{}\nrank #{} → {}",
+        total,
+        delta,
+        match position {
+            Some(position) => format!("lands as your #{} pass", position),
+            None => "doesn't beat your pass on this level".to_string(),
+        },
+        rank,
+        new_rank.map_or("unknown".to_string(), |rank| format!("#{}", rank)),
+    ))
+}
+
+/// The best score on every level the player passed, only those count towards ranked score.
+pub fn best_scores(passes: &[Pass]) -> HashMap<u32, f64> {
+    let mut best = HashMap::new();
+    for pass in passes {
+        let entry = best.entry(pass.level_id).or_insert(pass.score);
+        *entry = entry.max(pass.score);
+    }
+    best
+}
+
+/// Ranked score before and after adding the pass, and where the pass lands if it counts.
+/// Only the best pass on each level counts, like on TUF.
+pub fn ranked_impact(passes: &[Pass], level_id: u32, score: f64) -> (f64, f64, Option<usize>) {
+    let mut best = best_scores(passes);
+
+    let current = ranked_score(&best.values().copied().collect::<Vec<_>>());
+
+    let counts = best.get(&level_id).is_none_or(|existing| score > *existing);
+    if !counts {
+        return (current, current, None);
+    }
+    best.insert(level_id, score);
+
+    let scores = best.values().copied().collect::<Vec<_>>();
+    let position = scores.iter().filter(|other| **other > score).count() + 1;
+
+    (current, ranked_score(&scores), Some(position))
+}
+
+/// Lists what the same play is worth under every scoring model.
+pub fn models_field(
+    base_score: f64,
+    x_acc: f64,
+    tile_count: u32,
+    misses: u32,
+    speed: f64,
+) -> String {
+    MODELS
+        .iter()
+        .map(|model| {
+            format!(
+                "``{}``: **{:.2}**",
+                model.name(),
+                model.score(base_score, x_acc, tile_count, misses, speed)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The `model` option shared by the calculator commands.
+pub fn model_option() -> CreateCommandOption {
+    MODELS.iter().fold(
+        CreateCommandOption::new(
+            CommandOptionType::String,
+            "model",
+            "Scoring model to use (the current one by default)",
+        )
+        .required(false),
+        |option, model| option.add_string_choice(model.name(), model.id()),
+    )
+}
+
+pub fn selected_model(options: &[CommandDataOption]) -> &'static dyn ScoringModel {
+    options
+        .iter()
+        .find(|option| option.name == "model")
+        .and_then(|option| option.value.as_str())
+        .and_then(model_by_id)
+        .unwrap_or_else(default_model)
+}
+
 pub fn register() -> CreateCommand {
     let id = CreateCommandOption::new(
         serenity::all::CommandOptionType::Integer,
         "level_id",
         "The id for level you want to calculate",
     )
+    .set_autocomplete(true)
     .required(true);
 
     let x_acc = CreateCommandOption::new(
@@ -135,8 +341,8 @@ pub fn register() -> CreateCommand {
         "x_acc",
         "X-Accuracy",
     )
-    .min_int_value(0)
-    .max_int_value(100)
+    .min_number_value(0.)
+    .max_number_value(100.)
     .required(true);
 
     let misses = CreateCommandOption::new(
@@ -150,22 +356,24 @@ pub fn register() -> CreateCommand {
     let tile_count = CreateCommandOption::new(
         serenity::all::CommandOptionType::Integer,
         "tile_count",
-        "Your tile count",
+        "Your tile count (taken from TUF or /analyze when left out)",
     )
     .min_int_value(0)
-    .required(true);
+    .required(false);
 
     let speed = CreateCommandOption::new(
         serenity::all::CommandOptionType::Number,
         "speed",
         "Your speed",
     )
+    .min_number_value(MIN_SPEED)
+    .max_number_value(MAX_SPEED)
     .required(false);
 
     let ranked_position = CreateCommandOption::new(
         serenity::all::CommandOptionType::Integer,
         "ranked_position",
-        "Your ranked position",
+        "Your ranked position (used when your profile isn't linked)",
     )
     .min_int_value(0)
     .required(false);
@@ -174,5 +382,19 @@ pub fn register() -> CreateCommand {
         .description("Calculate your score")
         .dm_permission(true)
         .default_member_permissions(serenity::all::Permissions::empty())
-        .set_options(vec![id, x_acc, misses, tile_count, speed, ranked_position])
+        .set_options(vec![
+            id,
+            x_acc,
+            misses,
+            tile_count,
+            speed,
+            ranked_position,
+            model_option(),
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "compare",
+                "Show the score under every scoring model",
+            )
+            .required(false),
+        ])
 }
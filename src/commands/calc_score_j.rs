@@ -0,0 +1,162 @@
+use serenity::all::{
+    Color, CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    CreateEmbed, CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage,
+    EditInteractionResponse,
+};
+
+use crate::{
+    commands::{
+        calc_score::{model_option, selected_model},
+        calculator::{MAX_SPEED, MIN_SPEED},
+    },
+    formulas::{acc_by_judgement, breakdown_field},
+    judgement_parser::parse_judgements,
+    tuforums::level::get_level,
+    utils::{get_option_as_f64, get_option_as_string},
+};
+
+pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
+    let start_time = std::time::Instant::now();
+
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Defer(
+                CreateInteractionResponseMessage::new().content("Getting a beatmap..."),
+            ),
+        )
+        .await?;
+
+    let id = interaction
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == "level_id")
+        .and_then(|option| option.value.as_i64())
+        .unwrap_or(0) as u32;
+
+    let speed = get_option_as_f64(interaction, "speed", 1.);
+
+    let input = get_option_as_string(interaction, "judgements", "");
+
+    let judgements = match parse_judgements(&input) {
+        Ok(judgements) => judgements,
+        Err(e) => {
+            interaction
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new().content(e.render(&input)),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let level = match get_level(id).await {
+        Ok(map) => map,
+        Err(e) => {
+            interaction
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new().content(format!(
+                        "Failed to get the level with id: {}.\n**Error: {}**",
+                        id, e
+                    )),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let base_score = if level.score_base == 0. {
+        level.difficulty.score_base
+    } else {
+        level.score_base
+    };
+
+    let tile_count = judgements.tile_count() as u32;
+    let misses = judgements.misses() as u32;
+    let judgement_line = format!(
+        "```ansi
+[2;31m{}[0m [2;33m{}[0m [2;32m{}[0m [1;32m{}[0m [2;32m{}[0m [2;33m{}[0m [2;31m{}[0m```",
+        judgements.0,
+        judgements.1,
+        judgements.2,
+        judgements.3,
+        judgements.4,
+        judgements.5,
+        judgements.6,
+    );
+    let x_acc = acc_by_judgement(judgements) * 100.;
+
+    let breakdown = selected_model(&interaction.data.options)
+        .breakdown(base_score, x_acc, tile_count, misses, speed);
+
+    let stop_time = std::time::Instant::now();
+    let elapsed_time = stop_time.duration_since(start_time);
+
+    let embed = CreateEmbed::new()
+        .title(format!(
+            "{} - {} | ID: {}",
+            level.artist, level.title, level.id
+        ))
+        .description(format!("*charted by {}*", level.creator))
+        .field("Using those judgements", judgement_line, false)
+        .field(
+            "Calculated from judgements",
+            format!(
+                "``xAcc: {:.2}%`` | ``tile count: {}`` | ``misses: {}`` | ``speed: {}x``",
+                x_acc, tile_count, misses, speed
+            ),
+            false,
+        )
+        .thumbnail(level.difficulty.icon.to_string())
+        .field(
+            "Your score",
+            format!("**{:.2}**", breakdown.final_score),
+            true,
+        )
+        .field("Breakdown", breakdown_field(&breakdown), false)
+        .color(Color::from_rgb(
+            level.difficulty.color.0,
+            level.difficulty.color.1,
+            level.difficulty.color.2,
+        ))
+        .footer(CreateEmbedFooter::new(format!(
+            "Response time {} ms",
+            elapsed_time.as_millis()
+        )));
+
+    interaction
+        .edit_response(ctx, EditInteractionResponse::new().add_embed(embed))
+        .await?;
+
+    Ok(())
+}
+
+pub fn register() -> CreateCommand {
+    let id = CreateCommandOption::new(
+        CommandOptionType::Integer,
+        "level_id",
+        "The id for level you want to calculate",
+    )
+    .required(true);
+
+    let judgements = CreateCommandOption::new(
+        CommandOptionType::String,
+        "judgements",
+        "Judgement counts, e.g. 0 3 12 1200 10 2 0, perfect=1200 early=3 or the results text",
+    )
+    .required(true);
+
+    let speed = CreateCommandOption::new(CommandOptionType::Number, "speed", "Your speed")
+        .min_number_value(MIN_SPEED)
+        .max_number_value(MAX_SPEED)
+        .required(false);
+
+    CreateCommand::new("calcscore_j")
+        .description("Calculate your score from the judgements")
+        .dm_permission(true)
+        .default_member_permissions(serenity::all::Permissions::empty())
+        .set_options(vec![id, judgements, speed, model_option()])
+}
@@ -0,0 +1,384 @@
+use std::sync::Arc;
+
+use serenity::all::{
+    Color, CommandDataOption, CommandDataOptionValue, CommandInteraction, CommandOptionType,
+    Context, CreateCommand, CreateCommandOption, CreateEmbed, CreateEmbedFooter,
+    CreateInteractionResponse, CreateInteractionResponseMessage, EditInteractionResponse,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    cache_manager::CacheManager,
+    commands::{
+        calc_score::{best_scores, model_option, ranked_impact, selected_model},
+        profile::get_profile_linked,
+    },
+    database::Database,
+    formulas::{TargetSolution, max_misses_for_score, min_score_for_gain, min_x_acc_for_score},
+    tuforums::{level::get_level, profile::get_passes},
+};
+
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &Database,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) -> Result<(), serenity::Error> {
+    let start_time = std::time::Instant::now();
+
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Defer(
+                CreateInteractionResponseMessage::new().content("Getting a beatmap..."),
+            ),
+        )
+        .await?;
+
+    let Some(subcommand) = interaction.data.options.first() else {
+        return Ok(());
+    };
+
+    let options = match &subcommand.value {
+        CommandDataOptionValue::SubCommand(options) => options.as_slice(),
+        _ => &[],
+    };
+
+    let id = option_i64(options, "level_id").unwrap_or(0) as u32;
+    let target = option_f64(options, "target").unwrap_or(0.);
+    let tile_count = option_i64(options, "tile_count").unwrap_or(0) as u32;
+    let speed = option_f64(options, "speed").unwrap_or(1.);
+    let model = selected_model(options);
+
+    let level = match get_level(id).await {
+        Ok(map) => map,
+        Err(e) => {
+            interaction
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new().content(format!(
+                        "Failed to get the level with id: {}.\n**Error: {}**",
+                        id, e
+                    )),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let base_score = if level.score_base == 0. {
+        level.difficulty.score_base
+    } else {
+        level.score_base
+    };
+
+    let (inputs, result, curve) = match subcommand.name.as_str() {
+        "xacc" => {
+            let misses = option_i64(options, "misses").unwrap_or(0) as u32;
+            let solution =
+                min_x_acc_for_score(model, base_score, target, tile_count, misses, speed);
+            let (result, center) = x_acc_result(solution);
+
+            let curve = x_acc_curve(center, |x_acc| {
+                let score = model.score(base_score, x_acc, tile_count, misses, speed);
+                curve_line(format!("{:.2}%", x_acc), score, target)
+            });
+
+            (
+                format!(
+                    "``target: {}`` | ``misses: {}`` | ``tile count: {}`` | ``speed: {}x``",
+                    target, misses, tile_count, speed
+                ),
+                result,
+                curve,
+            )
+        }
+        "gain" => {
+            let misses = option_i64(options, "misses").unwrap_or(0) as u32;
+
+            let passes = match get_profile_linked(interaction.user.id.get() as i64, database).await
+            {
+                Ok(profile_id) => get_passes(profile_id, Some(cache_manager))
+                    .await
+                    .map_err(|e| format!("Failed to get your passes.\n**Error: {}**", e)),
+                Err(_) => Err(
+                    "❓ Link your profile with `/link` first, the gain depends on your passes."
+                        .to_string(),
+                ),
+            };
+            let passes = match passes {
+                Ok(passes) => passes,
+                Err(message) => {
+                    interaction
+                        .edit_response(ctx, EditInteractionResponse::new().content(message))
+                        .await?;
+                    return Ok(());
+                }
+            };
+
+            let mut best = best_scores(&passes);
+            let existing = best.remove(&level.id);
+            let others = best.into_values().collect::<Vec<_>>();
+            let score = min_score_for_gain(&others, existing, target);
+
+            let solution = min_x_acc_for_score(model, base_score, score, tile_count, misses, speed);
+            let (x_acc_result, center) = x_acc_result(solution);
+
+            let curve = x_acc_curve(center, |x_acc| {
+                let score = model.score(base_score, x_acc, tile_count, misses, speed);
+                let (current, new, _) = ranked_impact(&passes, level.id, score);
+                curve_line(format!("{:.2}%", x_acc), new - current, target)
+            });
+
+            (
+                format!(
+                    "``ranked gain: {}`` | ``misses: {}`` | ``tile count: {}`` | ``speed: {}x``",
+                    target, misses, tile_count, speed
+                ),
+                format!(
+                    "You need a pass worth at least **{:.2}**{}\n{}",
+                    score,
+                    existing.map_or(String::new(), |existing| format!(
+                        " (your pass here is worth {:.2})",
+                        existing
+                    )),
+                    x_acc_result
+                ),
+                curve,
+            )
+        }
+        "misses" => {
+            let x_acc = option_f64(options, "x_acc").unwrap_or(0.);
+            let solution =
+                max_misses_for_score(model, base_score, target, tile_count, x_acc, speed);
+
+            let result = match solution {
+                TargetSolution::Always => "Any amount of misses reaches this score 🎉".to_string(),
+                TargetSolution::Reached(misses) => {
+                    format!("You can miss at most **{}** times", misses)
+                }
+                TargetSolution::Unreachable(best) => format!(
+                    "❌ Unreachable, the best you can get is **{:.2}** with no misses",
+                    best
+                ),
+            };
+
+            let center = match solution {
+                TargetSolution::Reached(misses) => misses,
+                _ => 0,
+            };
+
+            let curve = (center.saturating_sub(3)..=center + 3)
+                .map(|misses| {
+                    let score = model.score(base_score, x_acc, tile_count, misses, speed);
+                    curve_line(format!("{} misses", misses), score, target)
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            (
+                format!(
+                    "``target: {}`` | ``xAcc: {}%`` | ``tile count: {}`` | ``speed: {}x``",
+                    target, x_acc, tile_count, speed
+                ),
+                result,
+                curve,
+            )
+        }
+        name => {
+            interaction
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new()
+                        .content(format!("⚠️ Unknown subcommand `{}`", name)),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let stop_time = std::time::Instant::now();
+    let elapsed_time = stop_time.duration_since(start_time);
+
+    let embed = CreateEmbed::new()
+        .title(format!(
+            "{} - {} | ID: {}",
+            level.artist, level.title, level.id
+        ))
+        .description(format!("*charted by {}*", level.creator))
+        .field(
+            "Using those informations",
+            format!(
+                "``model: {}``\n``base score: {}`` | {}",
+                model.name(),
+                base_score,
+                inputs
+            ),
+            false,
+        )
+        .thumbnail(level.difficulty.icon.to_string())
+        .field("Result", result, false)
+        .field("Around the target", format!("```{}```", curve), false)
+        .color(Color::from_rgb(
+            level.difficulty.color.0,
+            level.difficulty.color.1,
+            level.difficulty.color.2,
+        ))
+        .footer(CreateEmbedFooter::new(format!(
+            "Response time {} ms",
+            elapsed_time.as_millis()
+        )));
+
+    interaction
+        .edit_response(ctx, EditInteractionResponse::new().add_embed(embed))
+        .await?;
+
+    Ok(())
+}
+
+/// The text for an X-Accuracy solution and the accuracy the curve is centered on.
+fn x_acc_result(solution: TargetSolution<f64>) -> (String, f64) {
+    match solution {
+        TargetSolution::Always => ("Any X-Accuracy reaches this score 🎉".to_string(), 95.),
+        TargetSolution::Reached(x_acc) => (
+            format!("You need at least **{:.2}%** X-Accuracy", ceil_to(x_acc, 2)),
+            ceil_to(x_acc, 2),
+        ),
+        TargetSolution::Unreachable(best) => (
+            format!(
+                "❌ Unreachable, the best you can get is **{:.2}** with 100% X-Accuracy",
+                best
+            ),
+            100.,
+        ),
+    }
+}
+
+/// Lines for the accuracies around `center`, in steps of 0.1%.
+fn x_acc_curve(center: f64, line: impl Fn(f64) -> String) -> String {
+    (-3..=3)
+        .map(|step| center + step as f64 * 0.1)
+        .filter(|x_acc| (0. ..=100.).contains(x_acc))
+        .map(line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn option_i64(options: &[CommandDataOption], name: &str) -> Option<i64> {
+    options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.value.as_i64())
+}
+
+fn option_f64(options: &[CommandDataOption], name: &str) -> Option<f64> {
+    options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.value.as_f64())
+}
+
+/// Rounds up, so the shown accuracy never lands just below the target.
+fn ceil_to(value: f64, decimals: i32) -> f64 {
+    let factor = 10f64.powi(decimals);
+    ((value * factor).ceil() / factor).min(100.)
+}
+
+fn curve_line(label: String, score: f64, target: f64) -> String {
+    format!(
+        "{:<12} {:>10.2} {}",
+        label,
+        score,
+        if score >= target { "✅" } else { "❌" }
+    )
+}
+
+pub fn register() -> CreateCommand {
+    let level_id = || {
+        CreateCommandOption::new(
+            CommandOptionType::Integer,
+            "level_id",
+            "The id for level you want to calculate",
+        )
+        .required(true)
+    };
+
+    let target = || {
+        CreateCommandOption::new(CommandOptionType::Number, "target", "The score you want")
+            .min_number_value(0.)
+            .required(true)
+    };
+
+    let tile_count = || {
+        CreateCommandOption::new(CommandOptionType::Integer, "tile_count", "Your tile count")
+            .min_int_value(0)
+            .required(true)
+    };
+
+    let speed = || {
+        CreateCommandOption::new(CommandOptionType::Number, "speed", "Your speed").required(false)
+    };
+
+    let xacc = CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "xacc",
+        "Find the X-Accuracy you need for a score",
+    )
+    .add_sub_option(level_id())
+    .add_sub_option(target())
+    .add_sub_option(
+        CreateCommandOption::new(CommandOptionType::Integer, "misses", "Your misses")
+            .min_int_value(0)
+            .required(true),
+    )
+    .add_sub_option(tile_count())
+    .add_sub_option(speed())
+    .add_sub_option(model_option());
+
+    let misses = CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "misses",
+        "Find how many misses you can afford for a score",
+    )
+    .add_sub_option(level_id())
+    .add_sub_option(target())
+    .add_sub_option(
+        CreateCommandOption::new(CommandOptionType::Number, "x_acc", "X-Accuracy")
+            .min_number_value(0.)
+            .max_number_value(100.)
+            .required(true),
+    )
+    .add_sub_option(tile_count())
+    .add_sub_option(speed())
+    .add_sub_option(model_option());
+
+    let gain = CreateCommandOption::new(
+        CommandOptionType::SubCommand,
+        "gain",
+        "Find the X-Accuracy you need to gain ranked score",
+    )
+    .add_sub_option(level_id())
+    .add_sub_option(
+        CreateCommandOption::new(
+            CommandOptionType::Number,
+            "target",
+            "The ranked score you want to gain",
+        )
+        .min_number_value(0.)
+        .required(true),
+    )
+    .add_sub_option(
+        CreateCommandOption::new(CommandOptionType::Integer, "misses", "Your misses")
+            .min_int_value(0)
+            .required(true),
+    )
+    .add_sub_option(tile_count())
+    .add_sub_option(speed())
+    .add_sub_option(model_option());
+
+    CreateCommand::new("calctarget")
+        .description("Calculate what you need to reach a score or ranked score gain")
+        .dm_permission(true)
+        .default_member_permissions(serenity::all::Permissions::empty())
+        .set_options(vec![xacc, misses, gain])
+}
@@ -0,0 +1,504 @@
+use std::sync::Arc;
+
+use serenity::{
+    all::{
+        ActionRowComponent, ButtonStyle, Color, ComponentInteraction, Context, CreateActionRow,
+        CreateButton, CreateEmbed, CreateInputText, CreateInteractionResponse,
+        CreateInteractionResponseFollowup, CreateInteractionResponseMessage, CreateModal,
+        EditInteractionResponse, EventHandler, InputTextStyle, Interaction, ModalInteraction,
+        UserId,
+    },
+    async_trait,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    LogLevel,
+    cache_manager::CacheManager,
+    commands::calc_score::ranked_field,
+    database::Database,
+    formulas::{
+        breakdown_field,
+        scoring_model::{ScoringModel, default_model, model_by_id},
+    },
+    log_message,
+    tuforums::level::{Level, get_level},
+};
+
+/// Where the tile count came from, shown next to it in the embed.
+#[derive(Clone, Copy, PartialEq)]
+pub enum TileCountSource {
+    Given,
+    Tuf,
+    Analyze,
+}
+
+impl TileCountSource {
+    const ALL: [TileCountSource; 3] = [
+        TileCountSource::Given,
+        TileCountSource::Tuf,
+        TileCountSource::Analyze,
+    ];
+
+    fn id(&self) -> &'static str {
+        match self {
+            TileCountSource::Given => "g",
+            TileCountSource::Tuf => "t",
+            TileCountSource::Analyze => "a",
+        }
+    }
+
+    fn from_id(id: &str) -> Option<TileCountSource> {
+        TileCountSource::ALL
+            .into_iter()
+            .find(|source| source.id() == id)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TileCountSource::Given => "given",
+            TileCountSource::Tuf => "from TUF",
+            TileCountSource::Analyze => "from /analyze",
+        }
+    }
+}
+
+/// Everything the calculator needs to recompute a score.
+/// It lives in the `custom_id` of every button, so the message keeps working after a restart.
+#[derive(Clone, Copy)]
+pub struct CalculatorState {
+    pub level_id: u32,
+    /// Kept to 2 decimals, like `speed`, so the `custom_id` stays short.
+    pub x_acc: f64,
+    pub tile_count: u32,
+    pub tile_count_source: TileCountSource,
+    pub misses: u32,
+    pub speed: f64,
+    /// Used for the ranked score when the user's profile isn't linked.
+    pub ranked_position: u32,
+    pub model: &'static dyn ScoringModel,
+}
+
+impl CalculatorState {
+    /// Encodes the state as
+    /// `{prefix}:{action}:{level_id}:{x_acc}:{tile_count}:{source}:{misses}:{speed}:{ranked_position}:{model}`.
+    /// With the bounded command options this stays under Discord's 100 characters.
+    fn custom_id(&self, prefix: &str, action: &str) -> String {
+        format!(
+            "{}:{}:{}:{:.2}:{}:{}:{}:{:.2}:{}:{}",
+            prefix,
+            action,
+            self.level_id,
+            self.x_acc,
+            self.tile_count,
+            self.tile_count_source.id(),
+            self.misses,
+            self.speed,
+            self.ranked_position,
+            self.model.id()
+        )
+    }
+
+    /// Reads back a `custom_id` made by [`CalculatorState::custom_id`], returning the action too.
+    fn parse(custom_id: &str) -> Option<(&str, CalculatorState)> {
+        match custom_id.split(':').collect::<Vec<_>>().as_slice() {
+            [
+                "calc" | "calc_modal",
+                action,
+                level_id,
+                x_acc,
+                tile_count,
+                source,
+                misses,
+                speed,
+                ranked_position,
+                model,
+            ] => Some((
+                action,
+                CalculatorState {
+                    level_id: level_id.parse().ok()?,
+                    x_acc: round_2(x_acc.parse().ok()?),
+                    tile_count: tile_count.parse().ok()?,
+                    tile_count_source: TileCountSource::from_id(source)?,
+                    misses: misses.parse().ok()?,
+                    speed: round_2(speed.parse().ok()?),
+                    ranked_position: ranked_position.parse().ok()?,
+                    model: model_by_id(model).unwrap_or_else(default_model),
+                },
+            )),
+            _ => None,
+        }
+    }
+
+    /// Applies a button click. Returns `None` for the buttons that open a modal instead.
+    fn apply(mut self, action: &str) -> Option<CalculatorState> {
+        match action {
+            "speed_up" => self.speed = round_2(self.speed + 0.1).min(MAX_SPEED),
+            "speed_down" => self.speed = round_2(self.speed - 0.1).max(MIN_SPEED),
+            "miss_up" => self.misses = self.misses.saturating_add(1),
+            "miss_down" => self.misses = self.misses.saturating_sub(1),
+            _ => return None,
+        }
+        Some(self)
+    }
+}
+
+/// Bounds of the `speed` option, TUF doesn't rank speeds under 1.0x.
+pub const MIN_SPEED: f64 = 1.0;
+pub const MAX_SPEED: f64 = 10.0;
+
+/// Keeps repeated +0.1 clicks from drifting into values like 1.2000000000000002,
+/// and typed values from growing the `custom_id`.
+pub fn round_2(value: f64) -> f64 {
+    (value * 100.).round() / 100.
+}
+
+pub fn calculator_buttons(state: &CalculatorState) -> Vec<CreateActionRow> {
+    vec![
+        CreateActionRow::Buttons(vec![
+            CreateButton::new(state.custom_id("calc", "speed_down"))
+                .label("speed -0.1")
+                .style(ButtonStyle::Secondary)
+                .disabled(state.speed <= MIN_SPEED),
+            CreateButton::new(state.custom_id("calc", "speed_up"))
+                .label("speed +0.1")
+                .style(ButtonStyle::Secondary)
+                .disabled(state.speed >= MAX_SPEED),
+            CreateButton::new(state.custom_id("calc", "miss_down"))
+                .label("misses -1")
+                .style(ButtonStyle::Secondary)
+                .disabled(state.misses == 0),
+            CreateButton::new(state.custom_id("calc", "miss_up"))
+                .label("misses +1")
+                .style(ButtonStyle::Secondary),
+        ]),
+        CreateActionRow::Buttons(vec![
+            CreateButton::new(state.custom_id("calc", "x_acc"))
+                .label("Edit xAcc")
+                .style(ButtonStyle::Primary),
+            CreateButton::new(state.custom_id("calc", "tile_count"))
+                .label("Edit tile count")
+                .style(ButtonStyle::Primary),
+        ]),
+    ]
+}
+
+/// The score embed shared with `/calcscore`, which adds its own fields on top.
+pub fn calculator_embed(
+    level: &Level,
+    state: &CalculatorState,
+    ranked_field: String,
+) -> CreateEmbed {
+    let base_score = if level.score_base == 0. {
+        level.difficulty.score_base
+    } else {
+        level.score_base
+    };
+
+    let breakdown = state.model.breakdown(
+        base_score,
+        state.x_acc,
+        state.tile_count,
+        state.misses,
+        state.speed,
+    );
+
+    CreateEmbed::new()
+        .title(format!(
+            "{} - {} | ID: {}",
+            level.artist, level.title, level.id
+        ))
+        .description(format!("*charted by {}*", level.creator))
+        .field(
+            "Using those informations",
+            format!(
+                "``base score: {}`` | ``xAcc: {}``\n``tile Count: {}`` ({}) | ``misses: {}`` | ``speed: {}x``",
+                base_score,
+                state.x_acc,
+                state.tile_count,
+                state.tile_count_source.label(),
+                state.misses,
+                state.speed
+            ),
+            false,
+        )
+        .thumbnail(level.difficulty.icon.to_string())
+        .field(
+            "Your score",
+            format!("**{:.2}**", breakdown.final_score),
+            true,
+        )
+        .field("Your ranked score", ranked_field, true)
+        .field("Breakdown", breakdown_field(&breakdown), false)
+        .color(Color::from_rgb(
+            level.difficulty.color.0,
+            level.difficulty.color.1,
+            level.difficulty.color.2,
+        ))
+}
+
+fn edit_modal(state: &CalculatorState, field: &str) -> CreateModal {
+    let (title, label, value) = match field {
+        "x_acc" => ("Edit xAcc", "X-Accuracy (0-100)", state.x_acc.to_string()),
+        _ => (
+            "Edit tile count",
+            "Tile count",
+            state.tile_count.to_string(),
+        ),
+    };
+
+    CreateModal::new(state.custom_id("calc_modal", field), title).components(vec![
+        CreateActionRow::InputText(
+            CreateInputText::new(InputTextStyle::Short, label, "value")
+                .value(value)
+                .required(true),
+        ),
+    ])
+}
+
+/// Reads the edited value from the modal and applies it to the state.
+fn apply_modal(
+    mut state: CalculatorState,
+    field: &str,
+    modal: &ModalInteraction,
+) -> Result<CalculatorState, String> {
+    let value = modal
+        .data
+        .components
+        .iter()
+        .flat_map(|row| row.components.iter())
+        .find_map(|component| match component {
+            ActionRowComponent::InputText(input) => input.value.clone(),
+            _ => None,
+        })
+        .unwrap_or_default();
+    let value = value.trim().trim_end_matches('%');
+
+    match field {
+        "x_acc" => match value.parse::<f64>() {
+            Ok(x_acc) if (0. ..=100.).contains(&x_acc) => state.x_acc = round_2(x_acc),
+            _ => return Err(format!("`{}` is not an xAcc between 0 and 100", value)),
+        },
+        "tile_count" => match value.parse::<u32>() {
+            Ok(tile_count) if tile_count > 0 => {
+                state.tile_count = tile_count;
+                state.tile_count_source = TileCountSource::Given;
+            }
+            _ => return Err(format!("`{}` is not a valid tile count", value)),
+        },
+        _ => return Err("Unknown field".to_string()),
+    }
+
+    Ok(state)
+}
+
+async fn respond_error(ctx: &Context, interaction: &Interaction, message: String) {
+    let response = CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .content(format!("⚠️ {}", message))
+            .ephemeral(true),
+    );
+
+    let result = match interaction {
+        Interaction::Component(component) => component.create_response(&ctx.http, response).await,
+        Interaction::Modal(modal) => modal.create_response(&ctx.http, response).await,
+        _ => Ok(()),
+    };
+
+    if let Err(why) = result {
+        log_message(
+            format!("Failed to answer the calculator: {:?}", why).as_str(),
+            LogLevel::Warning,
+        );
+    }
+}
+
+pub struct CalculatorHandler {
+    pub database: Database,
+    pub cache_manager: Arc<Mutex<CacheManager>>,
+}
+
+/// Shown when the calculator fails after the interaction was acknowledged.
+fn error_followup(message: String) -> CreateInteractionResponseFollowup {
+    CreateInteractionResponseFollowup::new()
+        .content(format!("⚠️ {}", message))
+        .ephemeral(true)
+}
+
+impl CalculatorHandler {
+    async fn update_message(
+        &self,
+        state: &CalculatorState,
+        user_id: UserId,
+    ) -> Result<EditInteractionResponse, String> {
+        let level = get_level(state.level_id)
+            .await
+            .map_err(|e| format!("Failed to get the level: {}", e))?;
+
+        let score = state.model.score(
+            if level.score_base == 0. {
+                level.difficulty.score_base
+            } else {
+                level.score_base
+            },
+            state.x_acc,
+            state.tile_count,
+            state.misses,
+            state.speed,
+        );
+        let ranked = ranked_field(
+            user_id,
+            level.id,
+            score,
+            state.ranked_position,
+            &self.database,
+            &self.cache_manager,
+        )
+        .await;
+
+        Ok(EditInteractionResponse::new()
+            .embed(calculator_embed(&level, state, ranked))
+            .components(calculator_buttons(state)))
+    }
+
+    async fn handle_component(
+        &self,
+        ctx: &Context,
+        component: &ComponentInteraction,
+    ) -> Result<(), String> {
+        let (action, state) =
+            CalculatorState::parse(&component.data.custom_id).ok_or("Invalid calculator button")?;
+
+        let Some(state) = state.apply(action) else {
+            return component
+                .create_response(
+                    &ctx.http,
+                    CreateInteractionResponse::Modal(edit_modal(&state, action)),
+                )
+                .await
+                .map_err(|e| e.to_string());
+        };
+
+        // Fetching the level and the ranked score can take longer than Discord's 3 seconds.
+        component
+            .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let result = match self.update_message(&state, component.user.id).await {
+            Ok(message) => component.edit_response(&ctx.http, message).await.map(drop),
+            Err(message) => component
+                .create_followup(&ctx.http, error_followup(message))
+                .await
+                .map(drop),
+        };
+        log_failure(result);
+
+        Ok(())
+    }
+
+    async fn handle_modal(&self, ctx: &Context, modal: &ModalInteraction) -> Result<(), String> {
+        let (field, state) =
+            CalculatorState::parse(&modal.data.custom_id).ok_or("Invalid calculator modal")?;
+        let state = apply_modal(state, field, modal)?;
+
+        modal
+            .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let result = match self.update_message(&state, modal.user.id).await {
+            Ok(message) => modal.edit_response(&ctx.http, message).await.map(drop),
+            Err(message) => modal
+                .create_followup(&ctx.http, error_followup(message))
+                .await
+                .map(drop),
+        };
+        log_failure(result);
+
+        Ok(())
+    }
+}
+
+fn log_failure(result: Result<(), serenity::Error>) {
+    if let Err(why) = result {
+        log_message(
+            format!("Failed to update the calculator: {:?}", why).as_str(),
+            LogLevel::Warning,
+        );
+    }
+}
+
+#[async_trait]
+impl EventHandler for CalculatorHandler {
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let result = match &interaction {
+            Interaction::Component(component) if component.data.custom_id.starts_with("calc:") => {
+                self.handle_component(&ctx, component).await
+            }
+            Interaction::Modal(modal) if modal.data.custom_id.starts_with("calc_modal:") => {
+                self.handle_modal(&ctx, modal).await
+            }
+            _ => return,
+        };
+
+        if let Err(message) = result {
+            respond_error(&ctx, &interaction, message).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formulas::scoring_model::MODELS;
+
+    fn state() -> CalculatorState {
+        CalculatorState {
+            level_id: 1234,
+            x_acc: 98.76,
+            tile_count: 2500,
+            tile_count_source: TileCountSource::Tuf,
+            misses: 3,
+            speed: 1.2,
+            ranked_position: 5,
+            model: default_model(),
+        }
+    }
+
+    #[test]
+    fn custom_id_round_trips() {
+        let id = state().custom_id("calc", "miss_up");
+        let (action, parsed) = CalculatorState::parse(&id).unwrap();
+
+        assert_eq!(action, "miss_up");
+        assert_eq!(parsed.custom_id("calc", "miss_up"), id);
+        assert!(parsed.tile_count_source == TileCountSource::Tuf);
+        assert_eq!(parsed.model.id(), default_model().id());
+    }
+
+    #[test]
+    fn custom_id_fits_discord_limit() {
+        let longest = CalculatorState {
+            level_id: u32::MAX,
+            x_acc: 100.,
+            tile_count: u32::MAX,
+            misses: u32::MAX,
+            speed: MAX_SPEED,
+            ranked_position: u32::MAX,
+            ..state()
+        };
+
+        for &model in MODELS {
+            let id = CalculatorState { model, ..longest }.custom_id("calc_modal", "tile_count");
+            assert!(id.len() <= 100, "{} is {} characters", id, id.len());
+            assert!(CalculatorState::parse(&id).is_some());
+        }
+    }
+
+    #[test]
+    fn rejects_other_ids() {
+        assert!(CalculatorState::parse("calc:speed_up:1:99:100:5:1:v2").is_none());
+        assert!(CalculatorState::parse("lb:ranked:desc:2").is_none());
+    }
+}
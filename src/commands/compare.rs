@@ -0,0 +1,288 @@
+use std::{collections::HashMap, sync::Arc};
+
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    CreateEmbed, CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage,
+    EditInteractionResponse,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    LogLevel,
+    cache_manager::CacheManager,
+    commands::profile::get_profile_linked,
+    database::Database,
+    log_message,
+    tuforums::profile::{Pass, PassView, Profile, get_pass_history, get_profile},
+};
+
+/// Levels listed in the head to head section, the counts still cover all of them.
+const HEAD_TO_HEAD_LINES: usize = 10;
+
+/// Width of the name columns, longer names are cut.
+const NAME_WIDTH: usize = 12;
+
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &Database,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) -> Result<(), serenity::Error> {
+    let option = |name: &str| {
+        interaction
+            .data
+            .options
+            .iter()
+            .find(|option| option.name == name)
+            .and_then(|option| option.value.as_i64())
+    };
+
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Defer(
+                CreateInteractionResponseMessage::new().content("Comparing players..."),
+            ),
+        )
+        .await?;
+
+    let a = match option("a") {
+        Some(id) => Some(id as u64),
+        None => get_profile_linked(interaction.user.id.get() as i64, database)
+            .await
+            .ok(),
+    };
+
+    let response = match (a, option("b")) {
+        (None, _) => EditInteractionResponse::new().content(
+            "❓ Could not find a linked profile.\nPlease link your profile using `/link` or pick player `a`.",
+        ),
+        (_, None) => EditInteractionResponse::new().content("❓ Pick player `b` to compare with."),
+        (Some(a), Some(b)) => match compare_embed(a, b as u64, cache_manager).await {
+            Ok(embed) => EditInteractionResponse::new().content("").embed(embed),
+            Err(e) => {
+                log_message(
+                    format!("Couldn't compare {a} and {b}: {e}").as_str(),
+                    LogLevel::Error,
+                );
+                EditInteractionResponse::new().content(format!("Error comparing players: {}", e))
+            }
+        },
+    };
+
+    interaction.edit_response(ctx, response).await?;
+
+    Ok(())
+}
+
+/// The profile then the passes, one after the other so the passes are read from the
+/// player response the profile just cached instead of requesting it a second time.
+async fn player(
+    id: u64,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) -> Result<(Profile, Vec<Pass>), Box<dyn std::error::Error + Send + Sync>> {
+    let cache = Some(cache_manager.as_ref());
+    let (profile, _) = get_profile(id, cache).await?;
+    let passes = get_pass_history(id, PassView::Top, cache).await?;
+
+    Ok((profile, passes))
+}
+
+async fn compare_embed(
+    a: u64,
+    b: u64,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) -> Result<CreateEmbed, Box<dyn std::error::Error + Send + Sync>> {
+    let ((profile_a, passes_a), (profile_b, passes_b)) =
+        tokio::try_join!(player(a, cache_manager), player(b, cache_manager))?;
+
+    let (wins_a, wins_b, lines) = head_to_head(&passes_a, &passes_b);
+
+    Ok(CreateEmbed::new()
+        .title(format!("{} vs {}", profile_a.name, profile_b.name))
+        .description(stats_table(&profile_a, &profile_b))
+        .field(
+            format!(
+                "Head to head | {} - {} on {} levels",
+                wins_a,
+                wins_b,
+                lines.len()
+            ),
+            if lines.is_empty() {
+                "They haven't passed any level in common.".to_string()
+            } else {
+                format!(
+                    "```{:<20} {:>9} {:>9}\n{}```",
+                    "Level",
+                    short_name(&profile_a.name, 9),
+                    short_name(&profile_b.name, 9),
+                    lines
+                        .iter()
+                        .take(HEAD_TO_HEAD_LINES)
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                )
+            },
+            false,
+        )
+        .color(profile_a.stats.top_diff.color)
+        .footer(CreateEmbedFooter::new(format!(
+            "IDs: {} vs {} | Δ is left minus right",
+            a, b
+        ))))
+}
+
+fn short_name(name: &str, width: usize) -> String {
+    name.chars().take(width).collect()
+}
+
+/// Marks the side that did better, `higher_is_better` is false for ranks.
+fn winner(a: f64, b: f64, higher_is_better: bool) -> &'static str {
+    match a.total_cmp(&b) {
+        std::cmp::Ordering::Equal => " ",
+        ordering if (ordering.is_gt()) == higher_is_better => "◀",
+        _ => "▶",
+    }
+}
+
+/// Special tiers aren't on the PGU scale, they lose against any PGU tier.
+fn top_diff_order(profile: &Profile) -> f64 {
+    profile.stats.top_diff.order().map(f64::from).unwrap_or(-1.)
+}
+
+fn stats_table(a: &Profile, b: &Profile) -> String {
+    let rank = |profile: &Profile| match profile.stats.rank.0 {
+        0 => "-".to_string(),
+        rank => format!("#{}", rank),
+    };
+    let (rank_a, rank_b) = (a.stats.rank.0, b.stats.rank.0);
+
+    let mut rows = vec![(
+        "Rank",
+        rank(a),
+        rank(b),
+        if rank_a == 0 || rank_b == 0 {
+            "-".to_string()
+        } else {
+            format!("{:+}", rank_a - rank_b)
+        },
+        if rank_a == 0 || rank_b == 0 {
+            " "
+        } else {
+            winner(rank_a as f64, rank_b as f64, false)
+        },
+    )];
+
+    for (label, value_a, value_b, suffix) in [
+        ("Ranked", a.stats.ranked_score, b.stats.ranked_score, ""),
+        ("General", a.stats.general_score, b.stats.general_score, ""),
+        (
+            "X-Acc",
+            a.stats.avg_xacc * 100.,
+            b.stats.avg_xacc * 100.,
+            "%",
+        ),
+    ] {
+        rows.push((
+            label,
+            format!("{:.2}{}", value_a, suffix),
+            format!("{:.2}{}", value_b, suffix),
+            format!("{:+.2}", value_a - value_b),
+            winner(value_a, value_b, true),
+        ));
+    }
+
+    rows.push((
+        "Top diff",
+        a.stats.top_diff.name.clone(),
+        b.stats.top_diff.name.clone(),
+        "-".to_string(),
+        winner(top_diff_order(a), top_diff_order(b), true),
+    ));
+
+    let mut table = format!(
+        "```{:<9}{:>w$} {:>w$} {:>9}\n",
+        "",
+        short_name(&a.name, NAME_WIDTH),
+        short_name(&b.name, NAME_WIDTH),
+        "Δ",
+        w = NAME_WIDTH
+    );
+    for (label, value_a, value_b, delta, mark) in rows {
+        table.push_str(&format!(
+            "{:<9}{:>w$} {:>w$} {:>9} {}\n",
+            label,
+            value_a,
+            value_b,
+            delta,
+            mark,
+            w = NAME_WIDTH
+        ));
+    }
+    table.push_str("```");
+
+    table
+}
+
+/// Compares the best pass of each player on the levels they both passed, hardest first.
+/// Returns the win counts and one line per shared level.
+fn head_to_head(a: &[Pass], b: &[Pass]) -> (usize, usize, Vec<String>) {
+    let best_b = b
+        .iter()
+        .map(|pass| (pass.level_id, pass))
+        .collect::<HashMap<_, _>>();
+
+    let mut shared = a
+        .iter()
+        .filter_map(|pass| Some((pass, *best_b.get(&pass.level_id)?)))
+        .collect::<Vec<_>>();
+    shared.sort_by(|(a, _), (b, _)| {
+        b.difficulty_order
+            .cmp(&a.difficulty_order)
+            .then(b.score.total_cmp(&a.score))
+    });
+
+    let wins_a = shared
+        .iter()
+        .filter(|(pass_a, pass_b)| pass_a.score > pass_b.score)
+        .count();
+    let wins_b = shared
+        .iter()
+        .filter(|(pass_a, pass_b)| pass_b.score > pass_a.score)
+        .count();
+
+    let lines = shared
+        .iter()
+        .map(|(pass_a, pass_b)| {
+            format!(
+                "{:<20} {:>9.2} {:>9.2} {}",
+                short_name(&pass_a.level_title, 20),
+                pass_a.score,
+                pass_b.score,
+                winner(pass_a.score, pass_b.score, true)
+            )
+        })
+        .collect();
+
+    (wins_a, wins_b, lines)
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("compare")
+        .description("Compare two players side by side")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::Integer, "b", "Player to compare with")
+                .set_autocomplete(true)
+                .required(true),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "a",
+                "First player, your linked profile by default",
+            )
+            .set_autocomplete(true)
+            .required(false),
+        )
+}
@@ -6,12 +6,34 @@ pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(),
     let embed = CreateEmbed::new()
         .title("Help")
         .description("Commands available:")
+        .field("/analyze", "Analyze a .adofai chart", false)
         .field("/calcacc", "Calculate your accuracy", false)
-        .field("/calcscore", "Calculate your score", false)
+        .field(
+            "/calcscore",
+            "Calculate your score, then tweak it with the buttons",
+            false,
+        )
+        .field(
+            "/calcscore_j",
+            "Calculate your score from judgements",
+            false,
+        )
+        .field(
+            "/calctarget",
+            "Calculate what you need to reach a score or ranked score gain",
+            false,
+        )
         .field("/clear", "Get clear info", false)
-        .field("/leaderboard", "Get the leaderboard", false)
+        .field("/compare", "Compare two players side by side", false)
+        .field(
+            "/leaderboard",
+            "Get the leaderboard, or your server's linked members with `scope:server`",
+            false,
+        )
+        .field("/level", "Look up a level by ID or search", false)
+        .field("/notify", "Choose what the bot announces about you", false)
         .field("/ping", "Check if the bot is alive", false)
-        .field("/profile", "Get your profile", false)
+        .field("/profile", "Get a profile by ID, name or Discord user", false)
         .field("/randomlvl", "Get a random level", false)
         .color(0xFF69B4);
 
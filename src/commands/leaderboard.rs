@@ -1,24 +1,58 @@
 use serenity::{
     all::{
-        ButtonStyle, CommandInteraction, CommandOptionType, Context, CreateActionRow, CreateButton,
-        CreateCommand, CreateCommandOption, CreateEmbed, CreateEmbedFooter,
-        CreateInteractionResponse, CreateInteractionResponseMessage, EditInteractionResponse,
-        EventHandler, Interaction,
+        ButtonStyle, CommandInteraction, CommandOptionType, ComponentInteraction, Context,
+        CreateActionRow, CreateButton, CreateCommand, CreateCommandOption, CreateEmbed,
+        CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage,
+        EditInteractionResponse, EventHandler, Interaction,
     },
     async_trait,
 };
+use std::sync::Arc;
+use tokio::sync::Mutex;
 
-use crate::tuforums::leaderboard::get_leaderboard;
-// TODO: make buttons to change pages
-pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
-    let page = interaction
-        .data
-        .options
-        .get(0)
+use crate::{
+    LogLevel,
+    cache_manager::CacheManager,
+    commands::pagination::{
+        jump_modal, page_buttons_with_jump, parse_jump, parse_jump_button, parse_page, total_pages,
+    },
+    commands::profile::get_profile_linked,
+    commands::server_leaderboard::{ServerSort, get_server_standings, server_embed},
+    database::Database,
+    log_message,
+    tuforums::{
+        leaderboard::{LeaderboardEntry, LeaderboardSort, get_leaderboard},
+        profile::get_profile,
+    },
+};
+
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &Database,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) -> Result<(), serenity::Error> {
+    let option = |name: &str| {
+        interaction
+            .data
+            .options
+            .iter()
+            .find(|option| option.name == name)
+    };
+
+    let page = option("page")
         .and_then(|option| option.value.as_i64())
-        .unwrap_or(1) as u32;
+        .map(|page| u32::try_from(page).unwrap_or(u32::MAX))
+        .unwrap_or(1)
+        .max(1);
 
-    let offset = (page - 1) * 15;
+    let sort_id = option("sort")
+        .and_then(|option| option.value.as_str())
+        .unwrap_or("ranked");
+
+    let descending = option("order")
+        .and_then(|option| option.value.as_str())
+        .is_none_or(|order| order != "asc");
 
     interaction
         .create_response(
@@ -29,128 +63,469 @@ pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(),
         )
         .await?;
 
-    let leaders = match get_leaderboard(offset, 15).await {
-        Ok(leaders) => leaders,
+    let server_scope = option("scope").and_then(|option| option.value.as_str()) == Some("server");
+
+    if server_scope {
+        let response = match (interaction.guild_id, ServerSort::from_id(sort_id)) {
+            _ if option("page").is_some() || option("around").is_some() => {
+                EditInteractionResponse::new().content(
+                    "The server leaderboard is a single page, `page` and `around` only work with the global one.",
+                )
+            }
+            (None, _) => EditInteractionResponse::new()
+                .content("The server leaderboard only works in a server."),
+            (_, None) => EditInteractionResponse::new()
+                .content("The server leaderboard can't be sorted by passes or world's firsts."),
+            (Some(guild_id), Some(sort)) => {
+                match get_server_standings(ctx, guild_id, database, cache_manager, sort).await {
+                    Ok(standings) => EditInteractionResponse::new().embed(server_embed(
+                        "Server leaderboard",
+                        &standings,
+                        sort,
+                        SERVER_LIMIT,
+                        Some(interaction.user.id),
+                    )),
+                    Err(e) => EditInteractionResponse::new()
+                        .content(format!("Error fetching the server leaderboard: {}", e)),
+                }
+            }
+        };
+        interaction.edit_response(ctx, response).await?;
+        return Ok(());
+    }
+
+    let Some(sort) = LeaderboardSort::from_id(sort_id) else {
+        interaction
+            .edit_response(
+                ctx,
+                EditInteractionResponse::new()
+                    .content("Sorting by top difficulty only works with `scope:server`."),
+            )
+            .await?;
+        return Ok(());
+    };
+
+    let around = option("around").and_then(|option| option.value.as_i64());
+
+    if let Some(player_id) = around {
+        let response = if sort != LeaderboardSort::Ranked || !descending {
+            EditInteractionResponse::new().content(
+                "`around` opens the player's ranked page, it only works with the ranked sort, highest first.",
+            )
+        } else {
+            match around_message(player_id as u64, cache_manager).await {
+                Ok((embed, components)) => EditInteractionResponse::new()
+                    .embed(embed)
+                    .components(components),
+                Err(message) => EditInteractionResponse::new().content(message),
+            }
+        };
+        interaction.edit_response(ctx, response).await?;
+        return Ok(());
+    }
+
+    let response = match leaderboard_message(page, sort, descending, None).await {
+        Ok((embed, components, clamped)) => EditInteractionResponse::new()
+            .content(if clamped {
+                "⚠️ There aren't that many pages, here is the last one."
+            } else {
+                ""
+            })
+            .embed(embed)
+            .components(components),
         Err(e) => {
-            interaction
-                .edit_response(
-                    ctx,
-                    EditInteractionResponse::new()
-                        .content(format!("Error fetching leaderboard: {}", e)),
+            EditInteractionResponse::new().content(format!("Error fetching leaderboard: {}", e))
+        }
+    };
+
+    interaction.edit_response(ctx, response).await?;
+
+    Ok(())
+}
+
+const PER_PAGE: u32 = 15;
+
+/// Highest page the `page` option accepts, anything past the end shows the last page anyway.
+const MAX_PAGE: u32 = u32::MAX / PER_PAGE;
+
+/// Index of the first entry on `page`, saturating for pages jumped to past the end.
+fn offset(page: u32) -> u32 {
+    page.saturating_sub(1).saturating_mul(PER_PAGE)
+}
+
+/// Members shown on the server leaderboard, the caller is added below when further down.
+const SERVER_LIMIT: usize = 20;
+
+/// Button ids start with `lb:{sort}:{order}`, so the pager keeps the selected sort.
+fn prefix(sort: LeaderboardSort, descending: bool) -> String {
+    format!(
+        "lb:{}:{}",
+        sort.id(),
+        if descending { "desc" } else { "asc" }
+    )
+}
+
+/// Builds a leaderboard page. A page past the end shows the last page instead,
+/// and the returned flag tells the caller it happened.
+async fn leaderboard_message(
+    page: u32,
+    sort: LeaderboardSort,
+    descending: bool,
+    highlight: Option<u64>,
+) -> Result<(CreateEmbed, Vec<CreateActionRow>, bool), Box<dyn std::error::Error + Send + Sync>> {
+    let mut leaderboard =
+        get_leaderboard(offset(page), PER_PAGE as usize, sort, descending).await?;
+    let mut pages = total_pages(leaderboard.total, PER_PAGE);
+    let mut page = page;
+    let clamped = page > pages;
+
+    if clamped {
+        page = pages;
+        leaderboard = get_leaderboard(offset(page), PER_PAGE as usize, sort, descending).await?;
+        pages = total_pages(leaderboard.total, PER_PAGE);
+    }
+
+    let embed = embedos(
+        leaderboard.entries,
+        page,
+        pages,
+        sort,
+        descending,
+        highlight,
+    );
+    let components = vec![
+        page_buttons_with_jump(&prefix(sort, descending), page, pages),
+        CreateActionRow::Buttons(vec![
+            CreateButton::new("lb:me")
+                .label("📍 Me")
+                .style(ButtonStyle::Success),
+        ]),
+    ];
+
+    Ok((embed, components, clamped))
+}
+
+/// The ranked leaderboard page holding `player_id`, with their row highlighted
+/// and the score gaps to the players right above and below them.
+/// Errors are messages ready to show to the user.
+async fn around_message(
+    player_id: u64,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) -> Result<(CreateEmbed, Vec<CreateActionRow>), String> {
+    let (profile, _) = get_profile(player_id, Some(cache_manager))
+        .await
+        .map_err(|e| format!("Error fetching profile: {}", e))?;
+
+    let rank = profile.stats.rank.0;
+    if rank <= 0 {
+        return Err(format!(
+            "❓ {} isn't on the ranked leaderboard.",
+            profile.name
+        ));
+    }
+    let rank = rank as u32;
+
+    let page = (rank - 1) / PER_PAGE + 1;
+    let (embed, components, _) =
+        leaderboard_message(page, LeaderboardSort::Ranked, true, Some(player_id))
+            .await
+            .map_err(|e| format!("Error fetching leaderboard: {}", e))?;
+
+    let start = rank.saturating_sub(2);
+    let neighbours = get_leaderboard(start, 3, LeaderboardSort::Ranked, true)
+        .await
+        .map(|leaderboard| leaderboard.entries)
+        .unwrap_or_default();
+
+    let gaps = match neighbours
+        .iter()
+        .position(|entry| entry.player_id == player_id)
+    {
+        Some(index) => {
+            let player = &neighbours[index];
+            let above = index
+                .checked_sub(1)
+                .and_then(|above| neighbours.get(above))
+                .map(|above| {
+                    format!(
+                        "⬆️ **{}** is {:.2} ahead",
+                        above.name,
+                        above.ranked_score - player.ranked_score
+                    )
+                });
+            let below = neighbours.get(index + 1).map(|below| {
+                format!(
+                    "⬇️ **{}** is {:.2} behind",
+                    below.name,
+                    player.ranked_score - below.ranked_score
                 )
-                .await?;
-            return Ok(());
+            });
+
+            [above, below]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join("\n")
         }
+        None => String::new(),
     };
 
-    let embed = embedos(leaders, page);
+    let embed = if gaps.is_empty() {
+        embed
+    } else {
+        embed.field(format!("Around {} (#{})", profile.name, rank), gaps, false)
+    };
 
-    let lb_prev = CreateButton::new(format!("lb_previous:{}", page - 1))
-        .label("⬅️")
-        .style(ButtonStyle::Primary)
-        .disabled(true);
+    Ok((embed, components))
+}
 
-    let lb_next = CreateButton::new(format!("lb_next:{}", page + 1))
-        .label("➡️")
-        .style(ButtonStyle::Primary)
-        .disabled(false);
+pub struct LeaderboardHandler {
+    pub database: Database,
+    pub cache_manager: Arc<Mutex<CacheManager>>,
+}
 
-    interaction
-        .edit_response(
-            ctx,
-            EditInteractionResponse::new()
-                .embed(embed)
-                .components(vec![CreateActionRow::Buttons(vec![lb_prev, lb_next])]),
-        )
-        .await?;
+impl LeaderboardHandler {
+    /// Answers the "📍 Me" button with the caller's page, as an ephemeral message
+    /// so it doesn't replace a leaderboard someone else is browsing.
+    async fn show_me(&self, component: &ComponentInteraction) -> CreateInteractionResponse {
+        let player_id =
+            match get_profile_linked(component.user.id.get() as i64, &self.database).await {
+                Ok(player_id) => player_id,
+                Err(_) => {
+                    return error_response(
+                        "Could not find a linked profile, link yours with `/link`.".to_string(),
+                    );
+                }
+            };
 
-    Ok(())
+        match around_message(player_id, &self.cache_manager).await {
+            Ok((embed, components)) => CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .embed(embed)
+                    .components(components)
+                    .ephemeral(true),
+            ),
+            Err(message) => error_response(message),
+        }
+    }
+
+    async fn show_page(
+        page: u32,
+        sort: LeaderboardSort,
+        descending: bool,
+    ) -> CreateInteractionResponse {
+        match leaderboard_message(page, sort, descending, None).await {
+            Ok((embed, components, clamped)) => CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(if clamped {
+                        "⚠️ The leaderboard changed since this message was sent, here is the last page."
+                    } else {
+                        ""
+                    })
+                    .embed(embed)
+                    .components(components),
+            ),
+            Err(e) => error_response(format!("Error fetching leaderboard: {}", e)),
+        }
+    }
 }
 
-pub struct LeaderboardHandler;
+fn error_response(message: String) -> CreateInteractionResponse {
+    CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .content(format!("⚠️ {}", message))
+            .ephemeral(true),
+    )
+}
+
+/// Reads the sort and order back from a `lb:{sort}:{order}:...` id.
+fn parse_prefix(custom_id: &str) -> Option<(String, LeaderboardSort, bool)> {
+    match custom_id.split(':').collect::<Vec<_>>().as_slice() {
+        ["lb", sort, order @ ("desc" | "asc"), ..] => {
+            let sort = LeaderboardSort::from_id(sort)?;
+            let descending = *order == "desc";
+            Some((prefix(sort, descending), sort, descending))
+        }
+        _ => None,
+    }
+}
 
 #[async_trait]
 impl EventHandler for LeaderboardHandler {
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
-        if let Interaction::Component(component) = interaction {
-            if !component.data.custom_id.starts_with("lb_") {
-                return;
-            }
+        match interaction {
+            Interaction::Component(component) => {
+                let custom_id = component.data.custom_id.as_str();
 
-            let custom_id = component.data.custom_id.as_str();
-
-            match custom_id.split(':').collect::<Vec<_>>().as_slice() {
-                ["lb_previous", page_str] | ["lb_next", page_str] => {
-                    if let Ok(page) = page_str.parse::<u32>() {
-                        let offset = (page - 1) * 15;
-                        let leaders = get_leaderboard(offset, 15).await.unwrap_or_else(|_| vec![]);
-
-                        let embed = embedos(leaders, page);
-
-                        let lb_prev = CreateButton::new(format!("lb_previous:{}", page - 1))
-                            .label("⬅️")
-                            .style(ButtonStyle::Primary)
-                            .disabled(page <= 1);
-
-                        let lb_next = CreateButton::new(format!("lb_next:{}", page + 1))
-                            .label("➡️")
-                            .style(ButtonStyle::Primary);
-
-                        component
-                            .create_response(
-                                &ctx.http,
-                                CreateInteractionResponse::UpdateMessage(
-                                    CreateInteractionResponseMessage::new()
-                                        .embed(embed)
-                                        .components(vec![CreateActionRow::Buttons(vec![
-                                            lb_prev, lb_next,
-                                        ])]),
-                                ),
-                            )
-                            .await
-                            .expect("Failed to update leaderboard page");
+                let response = if custom_id == "lb:me" {
+                    self.show_me(&component).await
+                } else if custom_id.starts_with("lb_") {
+                    // Buttons from before the leaderboard had sorting.
+                    error_response(
+                        "This leaderboard changed since the message was sent, run `/leaderboard` again."
+                            .to_string(),
+                    )
+                } else if let Some((prefix, sort, descending)) = parse_prefix(custom_id) {
+                    if let Some(pages) = parse_jump_button(custom_id, &prefix) {
+                        CreateInteractionResponse::Modal(jump_modal(&prefix, pages))
+                    } else if let Some(page) = parse_page(custom_id, &prefix) {
+                        Self::show_page(page, sort, descending).await
+                    } else {
+                        error_response("Invalid button".to_string())
                     }
+                } else {
+                    return;
+                };
+
+                if let Err(why) = component.create_response(&ctx.http, response).await {
+                    log_message(
+                        format!("Failed to update leaderboard page: {:?}", why).as_str(),
+                        LogLevel::Warning,
+                    );
                 }
-                _ => {
-                    println!("Invalid button format");
+            }
+            Interaction::Modal(modal) => {
+                let Some((prefix, sort, descending)) = parse_prefix(&modal.data.custom_id) else {
+                    return;
+                };
+
+                let response = match parse_jump(&modal, &prefix) {
+                    Some(Ok(page)) => Self::show_page(page, sort, descending).await,
+                    Some(Err(message)) => error_response(message),
+                    None => return,
+                };
+
+                if let Err(why) = modal.create_response(&ctx.http, response).await {
+                    log_message(
+                        format!("Failed to jump to a leaderboard page: {:?}", why).as_str(),
+                        LogLevel::Warning,
+                    );
                 }
             }
+            _ => {}
         }
     }
 }
 
 pub fn register() -> CreateCommand {
+    let sort = LeaderboardSort::ALL.iter().fold(
+        CreateCommandOption::new(CommandOptionType::String, "sort", "What to rank players by")
+            .required(false),
+        |option, sort| option.add_string_choice(sort.label(), sort.id()),
+    )
+    .add_string_choice("Top difficulty (server only)", "topdiff");
+
     CreateCommand::new("leaderboard")
         .description("Get the leaderboard from the TUForums")
         .dm_permission(true)
         .add_option(
             CreateCommandOption::new(CommandOptionType::Integer, "page", "Page number")
                 .min_int_value(1)
+                .max_int_value(MAX_PAGE as u64)
+                .required(false),
+        )
+        .add_option(sort)
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::String, "order", "Sort order")
+                .add_string_choice("Highest first", "desc")
+                .add_string_choice("Lowest first", "asc")
                 .required(false),
         )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "scope",
+                "Whole TUF or this server's linked members",
+            )
+            .add_string_choice("Global", "global")
+            .add_string_choice("Server", "server")
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "around",
+                "Open the ranked page of this player",
+            )
+            .set_autocomplete(true)
+            .required(false),
+        )
 }
 
-fn embedos(leaders: Vec<(u64, String, f64, f64, u64)>, page: u32) -> CreateEmbed {
+/// Turns a country code like `US` into its flag emoji.
+fn flag(country: &str) -> String {
+    country
+        .to_uppercase()
+        .chars()
+        .filter(|c| c.is_ascii_uppercase())
+        .filter_map(|c| char::from_u32(0x1F1E6 + (c as u32 - 'A' as u32)))
+        .collect()
+}
+
+fn sort_value(entry: &LeaderboardEntry, sort: LeaderboardSort) -> String {
+    match sort {
+        LeaderboardSort::Ranked => format!("{:.2}", entry.ranked_score),
+        LeaderboardSort::General => format!("{:.2}", entry.general_score),
+        LeaderboardSort::XAcc => format!("{:.2}%", entry.avg_x_acc),
+        LeaderboardSort::Passes => format!("{} passes", entry.passes),
+        LeaderboardSort::WorldsFirsts => format!("{} WFs", entry.worlds_firsts),
+    }
+}
+
+fn embedos(
+    leaders: Vec<LeaderboardEntry>,
+    page: u32,
+    total_pages: u32,
+    sort: LeaderboardSort,
+    descending: bool,
+    highlight: Option<u64>,
+) -> CreateEmbed {
+    let offset = offset(page);
+
     CreateEmbed::new()
         .title("Leaderboard")
-        .field(
-            "``username [id] | ranked score | avg accuracy``",
-            format!(
-                "{}",
-                leaders
-                    .iter()
-                    .map(|(position, name, score, acc, id)| {
-                        let crown = if *position == 1 { "👑 " } else { "" };
-                        format!(
-                            "``{:<3}. {:<20}[{}] | {:.2} | {:.2}%`` {crown}",
-                            position, name, id, score, acc
-                        )
-                    })
-                    .collect::<Vec<String>>()
-                    .join("\n")
-            ),
-            false,
-        )
+        // A description rather than a field, the extra columns don't fit in 1024 characters.
+        .description(format!(
+            "**``username [id] | {} | avg accuracy``**\n{}",
+            sort.label().to_lowercase(),
+            leaders
+                .iter()
+                .enumerate()
+                .map(|(index, entry)| {
+                    let crown = if entry.rank == 1 { " 👑" } else { "" };
+                    let line = format!(
+                        "``{:<3}. {:<20}[{}] | {} | {:.2}%`` {}{}{}",
+                        offset as usize + index + 1,
+                        entry.name,
+                        entry.player_id,
+                        sort_value(entry, sort),
+                        entry.avg_x_acc,
+                        entry.top_diff.as_deref().unwrap_or(""),
+                        entry
+                            .country
+                            .as_deref()
+                            .map(|country| format!(" {}", flag(country)))
+                            .unwrap_or_default(),
+                        crown
+                    );
+
+                    if highlight == Some(entry.player_id) {
+                        format!("📍 **{}**", line)
+                    } else {
+                        line
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join("\n")
+        ))
         .color(0xFF69B4)
-        .footer(CreateEmbedFooter::new(format!("page {page}")))
+        .footer(CreateEmbedFooter::new(format!(
+            "page {page}/{total_pages} | sorted by {} ({})",
+            sort.label(),
+            if descending {
+                "highest first"
+            } else {
+                "lowest first"
+            }
+        )))
 }
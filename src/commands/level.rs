@@ -0,0 +1,375 @@
+use std::sync::Arc;
+
+use serenity::{
+    all::{
+        ButtonStyle, CommandInteraction, CommandOptionType, ComponentInteraction,
+        ComponentInteractionDataKind, Context, CreateActionRow, CreateButton, CreateCommand,
+        CreateCommandOption, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+        CreateInteractionResponseMessage, CreateSelectMenu, CreateSelectMenuKind,
+        CreateSelectMenuOption, EditInteractionResponse, EventHandler, Interaction,
+    },
+    async_trait,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    LogLevel,
+    cache_manager::{CacheManager, LiveTime},
+    commands::{
+        pagination::{page_buttons, parse_page, total_pages},
+        random_lvl::level_view,
+    },
+    log_message,
+    tuforums::{
+        difficulty::{DifficultyRange, DifficultyTier},
+        level::{LevelFilter, LevelSearchPage, get_level, search_levels},
+    },
+};
+
+const PER_PAGE: u32 = 10;
+
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) -> Result<(), serenity::Error> {
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Defer(
+                CreateInteractionResponseMessage::new().content("Looking for levels..."),
+            ),
+        )
+        .await?;
+
+    let option_str = |name: &str| {
+        interaction
+            .data
+            .options
+            .iter()
+            .find(|option| option.name == name)
+            .and_then(|option| option.value.as_str())
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    let id = interaction
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == "id")
+        .and_then(|option| option.value.as_i64());
+
+    if let Some(id) = id {
+        let response = match get_level(id as u32).await {
+            Ok(level) => {
+                let (embed, components) = level_view(level, Some(cache_manager)).await;
+                EditInteractionResponse::new()
+                    .embed(embed)
+                    .components(components)
+            }
+            Err(e) => EditInteractionResponse::new().content(format!(
+                "Failed to get the level with id: {}.\n**Error: {}**",
+                id, e
+            )),
+        };
+        interaction.edit_response(ctx, response).await?;
+        return Ok(());
+    }
+
+    let difficulty_range = match (option_str("min_diff"), option_str("max_diff")) {
+        (None, None) => None,
+        (min, max) => {
+            let tier = |name: Option<String>, default: DifficultyTier| match name {
+                Some(name) => name.parse::<DifficultyTier>(),
+                None => Ok(default),
+            };
+            let range = tier(min, DifficultyRange::ALL_PGU.min)
+                .and_then(|min| Ok((min, tier(max, DifficultyRange::ALL_PGU.max)?)))
+                .and_then(|(min, max)| DifficultyRange::new(min, max));
+
+            match range {
+                Ok(range) => Some(range),
+                Err(message) => {
+                    interaction
+                        .edit_response(
+                            ctx,
+                            EditInteractionResponse::new().content(format!("❌ {}", message)),
+                        )
+                        .await?;
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    let filter = LevelFilter {
+        query: option_str("search").unwrap_or_default(),
+        artist: option_str("artist"),
+        creator: option_str("creator"),
+        difficulty_range,
+        cleared: option_str("cleared").map(|cleared| cleared == "cleared"),
+        sort: None,
+    };
+
+    if filter.query.is_empty()
+        && filter.artist.is_none()
+        && filter.creator.is_none()
+        && filter.difficulty_range.is_none()
+        && filter.cleared.is_none()
+    {
+        interaction
+            .edit_response(
+                ctx,
+                EditInteractionResponse::new()
+                    .content("Give me an `id`, some `search` text or at least one filter."),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let page = match search_levels(&filter, 0, PER_PAGE).await {
+        Ok(page) => page,
+        Err(e) => {
+            interaction
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new()
+                        .content(format!("Failed to search levels.\n**Error: {}**", e)),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let response = match page.levels.as_slice() {
+        [] => EditInteractionResponse::new().content("No level matches this search."),
+        [level] if page.total == 1 => match get_level(level.id).await {
+            Ok(level) => {
+                let (embed, components) = level_view(level, Some(cache_manager)).await;
+                EditInteractionResponse::new()
+                    .embed(embed)
+                    .components(components)
+            }
+            Err(e) => EditInteractionResponse::new().content(format!(
+                "Failed to get the level with id: {}.\n**Error: {}**",
+                level.id, e
+            )),
+        },
+        _ => {
+            // The filters are too long for a custom_id, so buttons only carry the interaction id.
+            let search_id = interaction.id.get();
+            cache_manager.lock().await.add(
+                format!("level_search_{search_id}"),
+                filter,
+                Some(LiveTime::Minutes(15)),
+            );
+
+            let (embed, components) = results_message(search_id, &page, 1);
+            EditInteractionResponse::new()
+                .embed(embed)
+                .components(components)
+        }
+    };
+
+    interaction.edit_response(ctx, response).await?;
+
+    Ok(())
+}
+
+fn results_message(
+    search_id: u64,
+    page: &LevelSearchPage,
+    page_number: u32,
+) -> (CreateEmbed, Vec<CreateActionRow>) {
+    let offset = (page_number - 1) * PER_PAGE;
+    let pages = total_pages(page.total, PER_PAGE);
+
+    let embed = CreateEmbed::new()
+        .title("Levels")
+        .description(
+            page.levels
+                .iter()
+                .enumerate()
+                .map(|(index, level)| {
+                    format!(
+                        "``{:<3}.`` **{} - {}** ``{}`` | ID: {}",
+                        offset as usize + index + 1,
+                        level.artist,
+                        level.title,
+                        level.difficulty,
+                        level.id
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+        .color(0xFF69B4)
+        .footer(CreateEmbedFooter::new(format!(
+            "{} levels | page {}/{}",
+            page.total, page_number, pages
+        )));
+
+    let options = page
+        .levels
+        .iter()
+        .map(|level| {
+            CreateSelectMenuOption::new(
+                format!("{} - {}", level.artist, level.title)
+                    .chars()
+                    .take(100)
+                    .collect::<String>(),
+                level.id.to_string(),
+            )
+            .description(format!("ID: {} | {}", level.id, level.difficulty))
+        })
+        .collect::<Vec<_>>();
+
+    let components = vec![
+        CreateActionRow::SelectMenu(
+            CreateSelectMenu::new(
+                format!("lvl_pick:{search_id}:{page_number}"),
+                CreateSelectMenuKind::String { options },
+            )
+            .placeholder("Open a level"),
+        ),
+        page_buttons(&format!("lvl_search:{search_id}"), page_number, pages),
+    ];
+
+    (embed, components)
+}
+
+/// Handles the result pages and the level picker of `/level` searches.
+pub struct LevelSearchHandler {
+    pub cache_manager: Arc<Mutex<CacheManager>>,
+}
+
+impl LevelSearchHandler {
+    async fn show_page(
+        &self,
+        search_id: u64,
+        page_number: u32,
+    ) -> Result<CreateInteractionResponse, String> {
+        let filter = self
+            .cache_manager
+            .lock()
+            .await
+            .get::<LevelFilter>(format!("level_search_{search_id}").as_str())
+            .cloned()
+            .ok_or("This search has expired, run `/level` again.")?;
+
+        let page = search_levels(&filter, (page_number - 1) * PER_PAGE, PER_PAGE)
+            .await
+            .map_err(|e| format!("Failed to search levels: {}", e))?;
+
+        if page.levels.is_empty() {
+            return Err("This page is empty now.".to_string());
+        }
+
+        let (embed, components) = results_message(search_id, &page, page_number);
+        Ok(CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .embed(embed)
+                .components(components),
+        ))
+    }
+
+    async fn show_level(
+        &self,
+        component: &ComponentInteraction,
+        search_id: u64,
+        page_number: u32,
+    ) -> Result<CreateInteractionResponse, String> {
+        let level_id = match &component.data.kind {
+            ComponentInteractionDataKind::StringSelect { values } => values
+                .first()
+                .and_then(|value| value.parse::<u32>().ok())
+                .ok_or("No level picked")?,
+            _ => return Err("No level picked".to_string()),
+        };
+
+        let level = get_level(level_id)
+            .await
+            .map_err(|e| format!("Failed to get the level: {}", e))?;
+
+        let back = CreateButton::new(format!("lvl_search:{search_id}:page:{page_number}"))
+            .label("Back to results")
+            .style(ButtonStyle::Secondary);
+
+        let (embed, mut components) = level_view(level, Some(&self.cache_manager)).await;
+        components.push(CreateActionRow::Buttons(vec![back]));
+
+        Ok(CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .embed(embed)
+                .components(components),
+        ))
+    }
+}
+
+#[async_trait]
+impl EventHandler for LevelSearchHandler {
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let Interaction::Component(component) = interaction else {
+            return;
+        };
+
+        let custom_id = component.data.custom_id.as_str();
+        let parts = custom_id.split(':').collect::<Vec<_>>();
+
+        let response = match parts.as_slice() {
+            ["lvl_search", search_id, ..] => {
+                let prefix = format!("lvl_search:{search_id}");
+                match (search_id.parse::<u64>(), parse_page(custom_id, &prefix)) {
+                    (Ok(search_id), Some(page)) => self.show_page(search_id, page).await,
+                    _ => Err("Invalid button".to_string()),
+                }
+            }
+            ["lvl_pick", search_id, page] => {
+                match (search_id.parse::<u64>(), page.parse::<u32>()) {
+                    (Ok(search_id), Ok(page)) => self.show_level(&component, search_id, page).await,
+                    _ => Err("Invalid menu".to_string()),
+                }
+            }
+            _ => return,
+        };
+
+        let response = response.unwrap_or_else(|message| {
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(format!("⚠️ {}", message))
+                    .ephemeral(true),
+            )
+        });
+
+        if let Err(why) = component.create_response(&ctx.http, response).await {
+            log_message(
+                format!("Failed to answer a level search: {:?}", why).as_str(),
+                LogLevel::Warning,
+            );
+        }
+    }
+}
+
+pub fn register() -> CreateCommand {
+    let string_option = |name: &str, description: &str| {
+        CreateCommandOption::new(CommandOptionType::String, name, description).required(false)
+    };
+
+    CreateCommand::new("level")
+        .description("Look up a level by ID or search for one")
+        .dm_permission(true)
+        .set_options(vec![
+            CreateCommandOption::new(CommandOptionType::Integer, "id", "Level ID")
+                .set_autocomplete(true)
+                .required(false),
+            string_option("search", "Song, artist or creator to look for"),
+            string_option("min_diff", "Lowest difficulty, like G1"),
+            string_option("max_diff", "Highest difficulty, like U5"),
+            string_option("artist", "Only levels by this artist"),
+            string_option("creator", "Only levels by this creator"),
+            string_option("cleared", "Only cleared or uncleared levels")
+                .add_string_choice("Cleared", "cleared")
+                .add_string_choice("Uncleared", "uncleared"),
+        ])
+}
@@ -1,15 +1,27 @@
+use std::sync::Arc;
+
 use mongodb::bson::doc;
 use serenity::all::{
     CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
-    CreateInteractionResponse, CreateInteractionResponseMessage, EditInteractionResponse,
+    CreateInteractionResponse, CreateInteractionResponseFollowup, CreateInteractionResponseMessage,
+    EditInteractionResponse,
 };
+use tokio::sync::Mutex;
 
-use crate::{database::Database, tuforums::profile::get_profile};
+use crate::{
+    LogLevel,
+    cache_manager::CacheManager,
+    database::Database,
+    log_message,
+    tasks::role_sync::{apply, plan_member},
+    tuforums::profile::get_profile,
+};
 
 pub async fn run(
     ctx: &Context,
     interaction: &CommandInteraction,
     database: &Database,
+    cache_manager: &Arc<Mutex<CacheManager>>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let db = &database.client;
 
@@ -28,7 +40,7 @@ pub async fn run(
         )
         .await?;
 
-    if let Some(_) = db {
+    if db.is_some() {
         let profile = match get_profile(profile_id as u64, None).await {
             Ok(profile) => profile.0,
             Err(_) => {
@@ -65,11 +77,13 @@ pub async fn run(
                         &collection,
                         doc! {
                             "_id": user_id,
-                            "profile_id": profile_id as i64,
+                            "profile_id": profile_id,
                         },
                     )
                     .await
                     .expect("Failed to link profile");
+
+                    refresh_roles(ctx, interaction, database, cache_manager).await;
                 } else {
                     interaction
                         .edit_response(
@@ -144,6 +158,48 @@ pub async fn link_profile(
     Ok(())
 }
 
+/// Syncs the caller's roles in the server `/link` was used in, so they don't wait for the next sync.
+async fn refresh_roles(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &Database,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) {
+    let Some(guild_id) = interaction.guild_id else {
+        return;
+    };
+
+    match plan_member(ctx, guild_id, interaction.user.id, database, cache_manager).await {
+        Ok(plan) => {
+            let failures = apply(ctx, guild_id, &plan).await;
+            if failures.is_empty() {
+                return;
+            }
+
+            let followup = CreateInteractionResponseFollowup::new()
+                .content(format!(
+                    "⚠️ Some of your roles couldn't be updated, a server admin has to check the bot's permissions:\n{}",
+                    failures.join("\n")
+                ))
+                .ephemeral(true);
+            if let Err(e) = interaction.create_followup(ctx, followup).await {
+                log_message(
+                    format!("Couldn't report the role failures: {}", e).as_str(),
+                    LogLevel::Warning,
+                );
+            }
+        }
+        Err(e) => log_message(
+            format!(
+                "Couldn't refresh the roles of {}: {}",
+                interaction.user.id, e
+            )
+            .as_str(),
+            LogLevel::Warning,
+        ),
+    }
+}
+
 pub fn register() -> CreateCommand {
     CreateCommand::new("link")
         .description("Link your profile of TUF")
@@ -153,6 +209,7 @@ pub fn register() -> CreateCommand {
                 "profile_id",
                 "Your profile ID in TUF",
             )
+            .set_autocomplete(true)
             .required(true),
         )
 }
@@ -1,11 +1,21 @@
+pub mod analyze;
+pub mod autocomplete;
 pub mod cache_info;
 pub mod calc_acc;
 pub mod calc_score;
+pub mod calc_score_j;
+pub mod calc_target;
+pub mod calculator;
 pub mod clear;
+pub mod compare;
 pub mod help;
 pub mod leaderboard;
+pub mod level;
 pub mod link;
+pub mod notify;
+pub mod pagination;
 pub mod ping;
 pub mod profile;
 pub mod random_lvl;
+pub mod server_leaderboard;
 pub mod setup;
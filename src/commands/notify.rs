@@ -0,0 +1,95 @@
+use mongodb::bson::doc;
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    CreateInteractionResponse, CreateInteractionResponseMessage,
+};
+
+use crate::database::Database;
+
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    database: &Database,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let user_id = interaction.user.id.get() as i64;
+
+    let settings = interaction
+        .data
+        .options
+        .iter()
+        .filter_map(|option| Some((option.name.as_str(), option.value.as_bool()?)))
+        .collect::<Vec<_>>();
+
+    let content = match database.get_collection("onuzglorp-bot", "users").await {
+        None => "⚠️ Database connection error".to_string(),
+        Some(collection) => {
+            let user = collection.find_one(doc! { "_id": user_id }).await?;
+
+            match user {
+                None => "❓ Link your profile with `/link` first.".to_string(),
+                Some(user) => {
+                    let mut update = doc! {};
+                    for (name, enabled) in &settings {
+                        update.insert(format!("notify_{name}"), *enabled);
+                    }
+
+                    if !update.is_empty() {
+                        collection
+                            .update_one(doc! { "_id": user_id }, doc! { "$set": &update })
+                            .await?;
+                    }
+
+                    // Announcements are on until the user turns them off.
+                    let enabled = |name: &str| {
+                        update
+                            .get_bool(format!("notify_{name}"))
+                            .or_else(|_| user.get_bool(format!("notify_{name}")))
+                            .unwrap_or(true)
+                    };
+
+                    let state = |name: &str| if enabled(name) { "on" } else { "off" };
+
+                    format!(
+                        "🔔 Your notification settings:\nRank changes: **{}**\nNew passes: **{}**",
+                        state("rank_changes"),
+                        state("passes")
+                    )
+                }
+            }
+        }
+    };
+
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub fn register() -> CreateCommand {
+    CreateCommand::new("notify")
+        .description("Choose what the bot announces about you")
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "rank_changes",
+                "Announce your rank changes in the server's rank updates channel",
+            )
+            .required(false),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "passes",
+                "Post your new passes in the server's pass feed channel",
+            )
+            .required(false),
+        )
+}
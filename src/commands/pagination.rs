@@ -0,0 +1,117 @@
+use serenity::all::{
+    ActionRowComponent, ButtonStyle, CreateActionRow, CreateButton, CreateInputText, CreateModal,
+    InputTextStyle, ModalInteraction,
+};
+
+/// First/previous/next/last buttons for a paginated message, with ids like `{prefix}:prev:{page}`.
+/// The page in the id is the one the button leads to, so handlers don't need to keep state.
+pub fn page_buttons(prefix: &str, page: u32, total_pages: u32) -> CreateActionRow {
+    nav_buttons(prefix, page, total_pages, false)
+}
+
+/// Same as [`page_buttons`], but the page counter opens the [`jump_modal`] when clicked.
+pub fn page_buttons_with_jump(prefix: &str, page: u32, total_pages: u32) -> CreateActionRow {
+    nav_buttons(prefix, page, total_pages, true)
+}
+
+fn nav_buttons(prefix: &str, page: u32, total_pages: u32, jump: bool) -> CreateActionRow {
+    let total_pages = total_pages.max(1);
+
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("{prefix}:first:1"))
+            .label("⏮️")
+            .style(ButtonStyle::Primary)
+            .disabled(page <= 1),
+        CreateButton::new(format!("{prefix}:prev:{}", page.saturating_sub(1).max(1)))
+            .label("⬅️")
+            .style(ButtonStyle::Primary)
+            .disabled(page <= 1),
+        // The counter's id carries the page count instead, which the jump modal needs.
+        CreateButton::new(if jump {
+            format!("{prefix}:jump:{total_pages}")
+        } else {
+            format!("{prefix}:page:{page}")
+        })
+        .label(format!("{page}/{total_pages}"))
+        .style(ButtonStyle::Secondary)
+        .disabled(!jump),
+        CreateButton::new(format!("{prefix}:next:{}", page + 1))
+            .label("➡️")
+            .style(ButtonStyle::Primary)
+            .disabled(page >= total_pages),
+        CreateButton::new(format!("{prefix}:last:{total_pages}"))
+            .label("⏭️")
+            .style(ButtonStyle::Primary)
+            .disabled(page >= total_pages),
+    ])
+}
+
+/// Reads the target page from a button made by [`page_buttons`].
+pub fn parse_page(custom_id: &str, prefix: &str) -> Option<u32> {
+    let rest = custom_id.strip_prefix(prefix)?.strip_prefix(':')?;
+
+    match rest.split(':').collect::<Vec<_>>().as_slice() {
+        ["first" | "prev" | "next" | "last" | "page", page] => {
+            page.parse().ok().filter(|page| *page >= 1)
+        }
+        _ => None,
+    }
+}
+
+/// The page count, if `custom_id` is the page counter of [`page_buttons_with_jump`].
+pub fn parse_jump_button(custom_id: &str, prefix: &str) -> Option<u32> {
+    custom_id
+        .strip_prefix(prefix)?
+        .strip_prefix(":jump:")?
+        .parse()
+        .ok()
+}
+
+/// A modal asking for a page number, sent back with the id `{prefix}:jump_to:{total_pages}`.
+pub fn jump_modal(prefix: &str, total_pages: u32) -> CreateModal {
+    CreateModal::new(format!("{prefix}:jump_to:{total_pages}"), "Go to page").components(vec![
+        CreateActionRow::InputText(
+            CreateInputText::new(
+                InputTextStyle::Short,
+                format!("Page (1-{total_pages})"),
+                "page",
+            )
+            .required(true),
+        ),
+    ])
+}
+
+/// Reads the page typed in a [`jump_modal`]. Errors are messages ready to show to the user.
+pub fn parse_jump(modal: &ModalInteraction, prefix: &str) -> Option<Result<u32, String>> {
+    let total_pages = modal
+        .data
+        .custom_id
+        .strip_prefix(prefix)?
+        .strip_prefix(":jump_to:")?
+        .parse::<u32>()
+        .ok()?;
+
+    let value = modal
+        .data
+        .components
+        .iter()
+        .flat_map(|row| row.components.iter())
+        .find_map(|component| match component {
+            ActionRowComponent::InputText(input) => input.value.clone(),
+            _ => None,
+        })
+        .unwrap_or_default();
+
+    Some(match value.trim().parse::<u32>() {
+        Ok(page) if (1..=total_pages).contains(&page) => Ok(page),
+        _ => Err(format!(
+            "`{}` isn't a page between 1 and {}",
+            value.trim(),
+            total_pages
+        )),
+    })
+}
+
+pub fn total_pages(total: u64, per_page: u32) -> u32 {
+    total.div_ceil(per_page as u64).max(1) as u32
+}
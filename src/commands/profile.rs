@@ -1,12 +1,30 @@
 use std::sync::Arc;
 
 use mongodb::bson::doc;
-use serenity::all::{
-    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage, EditInteractionResponse
+use serenity::{
+    all::{
+        ButtonStyle, CommandInteraction, CommandOptionType, ComponentInteraction,
+        ComponentInteractionDataKind, Context, CreateActionRow, CreateButton, CreateCommand,
+        CreateCommandOption, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+        CreateInteractionResponseMessage, CreateSelectMenu, CreateSelectMenuKind,
+        CreateSelectMenuOption, EditInteractionResponse, EventHandler, Interaction,
+    },
+    async_trait,
 };
 use tokio::sync::Mutex;
 
-use crate::{cache_manager::CacheManager, database::Database, log_message, tuforums::profile::get_profile, LogLevel};
+use crate::{
+    LogLevel,
+    cache_manager::CacheManager,
+    commands::pagination::{page_buttons, parse_page, total_pages},
+    database::Database,
+    formulas::ranked_weight,
+    log_message,
+    tuforums::{
+        leaderboard::{PlayerSearchResult, search_players},
+        profile::{Pass, PassView, get_pass_history, get_profile},
+    },
+};
 
 pub async fn run(
     ctx: &Context,
@@ -16,35 +34,6 @@ pub async fn run(
 ) -> Result<(), serenity::Error> {
     let start_time = std::time::Instant::now();
 
-    let id = interaction
-        .data
-        .options
-        .get(0)
-        .and_then(|option| option.value.as_i64())
-        .unwrap_or(0) as u64;
-
-    let id = if id == 0 {
-        let discord_id = interaction.user.id.get() as i64;
-        match get_profile_linked(discord_id, database).await {
-            Ok(profile_id) => profile_id,
-            Err(_) => {
-                interaction
-                    .create_response(
-                        ctx,
-                        CreateInteractionResponse::Message(
-                            CreateInteractionResponseMessage::new()
-                                .content("❓ Could not find a linked profile.\nPlease link your profile using `/link`."),
-                        ),
-                    )
-                    .await
-                    .unwrap();
-                return Ok(());
-            }
-        }
-    } else {
-        id
-    };
-
     interaction
         .create_response(
             ctx,
@@ -55,72 +44,41 @@ pub async fn run(
         .await
         .unwrap();
 
-    match get_profile(id, Some(&cache_manager)).await {
-        Ok(result) => {
-            let profile = result.0;
-            let cached = result.1;
-            let stop_time = std::time::Instant::now();
-            let elapsed_time = stop_time.duration_since(start_time);
-
-            let embed = CreateEmbed::new()
-                .title(format!("Profile of {} {}", profile.name, profile.username))
-                .thumbnail(profile.avatar)
-                .field(
-                    "Rank",
-                    {
-                        let rank_str = profile.stats.rank.0.to_string();
-                        format!(
-                            "**#{}**",
-                            if profile.stats.rank.0 == 1 {
-                                "1 👑"
-                            } else {
-                                &rank_str
-                            }
-                        )
-                    },
-                    true,
-                )
-                .field(
-                    "Ranked Score",
-                    format!("**{:.2}**", profile.stats.ranked_score),
-                    true,
-                )
-                .field(
-                    "General Score",
-                    format!("**{:.2}**", profile.stats.general_score),
-                    true,
-                )
-                .field(
-                    "Top Diff",
-                    format!("**{}**", profile.stats.top_diff.name),
-                    true,
-                )
-                .field(
-                    "AVG X-Accuracy",
-                    format!("**{:.2}%**", profile.stats.avg_xacc * 100.),
-                    true,
-                )
-                .field(
-                    "Discord",
-                    format!(
-                        "**{}**",
-                        if let Some(id) = profile.discord_id {
-                            format!("<@{}>", id)
-                        } else {
-                            "Not linked".to_string()
-                        }
-                    ),
-                    true,
+    let id = match resolve_player(interaction, database).await {
+        Ok(PlayerLookup::Found(id)) => id,
+        Ok(PlayerLookup::Choose(players)) => {
+            interaction
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new()
+                        .content(format!(
+                            "🔎 {} players match this name, pick one:",
+                            players.len()
+                        ))
+                        .components(vec![player_picker(&players)]),
                 )
-                .color(profile.stats.top_diff.color)
-                .footer(CreateEmbedFooter::new(format!(
-                    "Response time: {} ms | Cache used: {}",
-                    elapsed_time.as_millis(),
-                    if cached { "Yes" } else { "No" }
-                )));
+                .await
+                .unwrap();
+            return Ok(());
+        }
+        Err(message) => {
+            interaction
+                .edit_response(ctx, EditInteractionResponse::new().content(message))
+                .await
+                .unwrap();
+            return Ok(());
+        }
+    };
 
+    match profile_message(id, cache_manager, start_time).await {
+        Ok((embed, components)) => {
             interaction
-                .edit_response(ctx, EditInteractionResponse::new().embed(embed))
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new()
+                        .embed(embed)
+                        .components(components),
+                )
                 .await
                 .unwrap();
         }
@@ -133,7 +91,10 @@ pub async fn run(
                 )
                 .await
                 .unwrap();
-            log_message(format!("Couldn't fetch profile {e}").as_str(), LogLevel::Error);
+            log_message(
+                format!("Couldn't fetch profile {e}").as_str(),
+                LogLevel::Error,
+            );
             return Ok(());
         }
     };
@@ -141,7 +102,354 @@ pub async fn run(
     Ok(())
 }
 
-async fn get_profile_linked(
+enum PlayerLookup {
+    Found(u64),
+    Choose(Vec<PlayerSearchResult>),
+}
+
+/// Finds the player from `player_id`, `user` or `name`, in that order, and falls back
+/// to the caller's linked profile. Errors are messages ready to show to the user.
+async fn resolve_player(
+    interaction: &CommandInteraction,
+    database: &Database,
+) -> Result<PlayerLookup, String> {
+    let option = |name: &str| {
+        interaction
+            .data
+            .options
+            .iter()
+            .find(|option| option.name == name)
+    };
+
+    if let Some(id) = option("player_id").and_then(|option| option.value.as_i64()) {
+        return Ok(PlayerLookup::Found(id as u64));
+    }
+
+    if let Some(user_id) = option("user").and_then(|option| option.value.as_user_id()) {
+        return get_profile_linked(user_id.get() as i64, database)
+            .await
+            .map(PlayerLookup::Found)
+            .map_err(|_| format!("❓ <@{}> hasn't linked a profile yet.", user_id));
+    }
+
+    if let Some(name) = option("name").and_then(|option| option.value.as_str()) {
+        let players = search_players(name.trim(), 25)
+            .await
+            .map_err(|e| format!("Couldn't search players: {}", e))?;
+
+        let exact = players
+            .iter()
+            .filter(|player| player.name.eq_ignore_ascii_case(name.trim()))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        return match (exact.as_slice(), players.as_slice()) {
+            ([player], _) | (_, [player]) => Ok(PlayerLookup::Found(player.id)),
+            (_, []) => Err(format!("❓ No player matches `{}`.", name.trim())),
+            _ => Ok(PlayerLookup::Choose(players)),
+        };
+    }
+
+    get_profile_linked(interaction.user.id.get() as i64, database)
+        .await
+        .map(PlayerLookup::Found)
+        .map_err(|_| {
+            "❓ Could not find a linked profile.\nPlease link your profile using `/link`."
+                .to_string()
+        })
+}
+
+fn player_picker(players: &[PlayerSearchResult]) -> CreateActionRow {
+    let options = players
+        .iter()
+        .map(|player| {
+            let rank = match player.rank {
+                0 => "unranked".to_string(),
+                rank => format!("#{}", rank),
+            };
+            CreateSelectMenuOption::new(
+                player.name.chars().take(100).collect::<String>(),
+                player.id.to_string(),
+            )
+            .description(format!("{} | ID: {}", rank, player.id))
+        })
+        .collect();
+
+    CreateActionRow::SelectMenu(
+        CreateSelectMenu::new("prof_pick", CreateSelectMenuKind::String { options })
+            .placeholder("Pick a player"),
+    )
+}
+
+/// The profile embed with the pass history buttons.
+async fn profile_message(
+    id: u64,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+    start_time: std::time::Instant,
+) -> Result<(CreateEmbed, Vec<CreateActionRow>), Box<dyn std::error::Error + Send + Sync>> {
+    let (profile, cached) = get_profile(id, Some(cache_manager)).await?;
+    let stop_time = std::time::Instant::now();
+    let elapsed_time = stop_time.duration_since(start_time);
+
+    let embed = CreateEmbed::new()
+        .title(format!("Profile of {} {}", profile.name, profile.username))
+        .thumbnail(profile.avatar)
+        .field(
+            "Rank",
+            {
+                let rank_str = profile.stats.rank.0.to_string();
+                format!(
+                    "**#{}**",
+                    if profile.stats.rank.0 == 1 {
+                        "1 👑"
+                    } else {
+                        &rank_str
+                    }
+                )
+            },
+            true,
+        )
+        .field(
+            "Ranked Score",
+            format!("**{:.2}**", profile.stats.ranked_score),
+            true,
+        )
+        .field(
+            "General Score",
+            format!("**{:.2}**", profile.stats.general_score),
+            true,
+        )
+        .field(
+            "Top Diff",
+            format!("**{}**", profile.stats.top_diff.name),
+            true,
+        )
+        .field(
+            "AVG X-Accuracy",
+            format!("**{:.2}%**", profile.stats.avg_xacc * 100.),
+            true,
+        )
+        .field(
+            "Discord",
+            format!(
+                "**{}**",
+                if let Some(id) = profile.discord_id {
+                    format!("<@{}>", id)
+                } else {
+                    "Not linked".to_string()
+                }
+            ),
+            true,
+        )
+        .color(profile.stats.top_diff.color)
+        .footer(CreateEmbedFooter::new(format!(
+            "Response time: {} ms | Cache used: {}",
+            elapsed_time.as_millis(),
+            if cached { "Yes" } else { "No" }
+        )));
+
+    Ok((embed, vec![view_buttons(id, "prof_passes_open", None)]))
+}
+
+const PASSES_PER_PAGE: u32 = 10;
+
+/// One button per [`PassView`], with ids like `{prefix}:{player_id}:{view}`.
+fn view_buttons(player_id: u64, prefix: &str, current: Option<PassView>) -> CreateActionRow {
+    CreateActionRow::Buttons(
+        PassView::ALL
+            .iter()
+            .map(|view| {
+                CreateButton::new(format!("{prefix}:{player_id}:{}", view.id()))
+                    .label(view.label())
+                    .style(if current == Some(*view) {
+                        ButtonStyle::Primary
+                    } else {
+                        ButtonStyle::Secondary
+                    })
+                    .disabled(current == Some(*view))
+            })
+            .collect(),
+    )
+}
+
+fn pass_line(pass: &Pass, position: usize, view: PassView) -> String {
+    let weight = if view == PassView::Top && position < 20 {
+        format!(
+            " | weighted **{:.2}** (x{:.2})",
+            pass.score * ranked_weight(position as u32),
+            ranked_weight(position as u32)
+        )
+    } else {
+        String::new()
+    };
+
+    format!(
+        "``{:<3}.`` **{} - {}** ``{}``{}\n-# {:.2} | {:.2}% | {}x | {} | [``/clear {}``](https://tuforums.com/passes/{}){}",
+        position,
+        pass.level_artist,
+        pass.level_title,
+        pass.difficulty,
+        if pass.is_worlds_first { " 🏆" } else { "" },
+        pass.score,
+        pass.x_acc,
+        pass.speed,
+        pass.uploaded.map_or("unknown".to_string(), |date| date
+            .format("%b %d, %Y")
+            .to_string()),
+        pass.id,
+        pass.id,
+        weight,
+    )
+}
+
+fn pass_history_page(
+    player_id: u64,
+    view: PassView,
+    passes: &[Pass],
+    page: u32,
+) -> CreateInteractionResponseMessage {
+    let pages = total_pages(passes.len() as u64, PASSES_PER_PAGE);
+    let page = page.min(pages);
+    let offset = ((page - 1) * PASSES_PER_PAGE) as usize;
+    let end = (offset + PASSES_PER_PAGE as usize).min(passes.len());
+
+    let description = if passes.is_empty() {
+        "Nothing to show here.".to_string()
+    } else {
+        passes[offset..end]
+            .iter()
+            .enumerate()
+            .map(|(index, pass)| pass_line(pass, offset + index + 1, view))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let embed = CreateEmbed::new()
+        .title(format!("{} of player {}", view.label(), player_id))
+        .url(format!("https://tuforums.com/profile/{}", player_id))
+        .description(description)
+        .color(0xFF69B4)
+        .footer(CreateEmbedFooter::new(format!(
+            "{} passes | page {}/{}",
+            passes.len(),
+            page,
+            pages
+        )));
+
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
+        .components(vec![
+            view_buttons(player_id, "prof_passes", Some(view)),
+            page_buttons(
+                &format!("prof_passes:{player_id}:{}", view.id()),
+                page,
+                pages,
+            ),
+        ])
+}
+
+/// Handles the `/profile` player picker, and opens and pages through a player's pass history.
+/// Like the clears browser, the pass history opens as an ephemeral message.
+pub struct ProfileHandler {
+    pub cache_manager: Arc<Mutex<CacheManager>>,
+}
+
+impl ProfileHandler {
+    async fn pick_player(&self, component: &ComponentInteraction) -> CreateInteractionResponse {
+        let start_time = std::time::Instant::now();
+
+        let player_id = match &component.data.kind {
+            ComponentInteractionDataKind::StringSelect { values } => {
+                values.first().and_then(|value| value.parse::<u64>().ok())
+            }
+            _ => None,
+        };
+
+        let result = match player_id {
+            Some(id) => profile_message(id, &self.cache_manager, start_time).await,
+            None => Err("No player picked".into()),
+        };
+
+        match result {
+            Ok((embed, components)) => CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content("")
+                    .embed(embed)
+                    .components(components),
+            ),
+            Err(e) => CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(format!("⚠️ Error fetching profile: {}", e))
+                    .ephemeral(true),
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for ProfileHandler {
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let Interaction::Component(component) = interaction else {
+            return;
+        };
+
+        let custom_id = component.data.custom_id.as_str();
+
+        if custom_id == "prof_pick" {
+            let response = self.pick_player(&component).await;
+            if let Err(why) = component.create_response(&ctx.http, response).await {
+                log_message(
+                    format!("Failed to answer the player picker: {:?}", why).as_str(),
+                    LogLevel::Warning,
+                );
+            }
+            return;
+        }
+
+        let (player_id, view, page, open) =
+            match custom_id.split(':').collect::<Vec<_>>().as_slice() {
+                ["prof_passes_open", player_id, view] => (*player_id, *view, Some(1), true),
+                ["prof_passes", player_id, view] => (*player_id, *view, Some(1), false),
+                ["prof_passes", player_id, view, ..] => (
+                    *player_id,
+                    *view,
+                    parse_page(custom_id, &format!("prof_passes:{player_id}:{view}")),
+                    false,
+                ),
+                _ => return,
+            };
+
+        let (Ok(player_id), Some(view), Some(page)) =
+            (player_id.parse::<u64>(), PassView::from_id(view), page)
+        else {
+            log_message("Invalid pass history button", LogLevel::Warning);
+            return;
+        };
+
+        let response = match get_pass_history(player_id, view, Some(&self.cache_manager)).await {
+            Ok(passes) if open => CreateInteractionResponse::Message(
+                pass_history_page(player_id, view, &passes, page).ephemeral(true),
+            ),
+            Ok(passes) => CreateInteractionResponse::UpdateMessage(pass_history_page(
+                player_id, view, &passes, page,
+            )),
+            Err(e) => CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(format!("⚠️ Couldn't fetch the passes: {}", e))
+                    .ephemeral(true),
+            ),
+        };
+
+        if let Err(why) = component.create_response(&ctx.http, response).await {
+            log_message(
+                format!("Failed to answer the pass history: {:?}", why).as_str(),
+                LogLevel::Warning,
+            );
+        }
+    }
+}
+
+pub async fn get_profile_linked(
     discord_id: i64,
     database: &Database,
 ) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
@@ -153,10 +461,10 @@ async fn get_profile_linked(
     let filter = doc! { "_id": discord_id };
     let user = collection.find_one(filter).await?;
 
-    if let Some(user) = user {
-        if let Some(profile_id) = user.get_i64("profile_id").ok() {
-            return Ok(profile_id as u64);
-        }
+    if let Some(user) = user
+        && let Ok(profile_id) = user.get_i64("profile_id")
+    {
+        return Ok(profile_id as u64);
     }
 
     Err("User not found".into())
@@ -165,9 +473,18 @@ async fn get_profile_linked(
 pub fn register() -> CreateCommand {
     CreateCommand::new("profile")
         .description("Get a user's profile")
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::Integer, "player_id", "Player ID")
+                .set_autocomplete(true),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "name",
+            "Player name on TUF",
+        ))
         .add_option(CreateCommandOption::new(
-            CommandOptionType::Integer,
-            "player_id",
-            "Player ID",
+            CommandOptionType::User,
+            "user",
+            "A Discord user with a linked profile",
         ))
 }
@@ -1,14 +1,32 @@
-use serenity::all::{
-    CommandInteraction, Context, CreateCommand, CreateEmbed, CreateEmbedAuthor,
-    CreateInteractionResponse, CreateInteractionResponseMessage, EditInteractionResponse,
+use std::sync::Arc;
+
+use serenity::{
+    all::{
+        ButtonStyle, CommandInteraction, Context, CreateActionRow, CreateButton, CreateCommand,
+        CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter, CreateInteractionResponse,
+        CreateInteractionResponseMessage, EditInteractionResponse, EventHandler, Interaction,
+    },
+    async_trait,
 };
+use tokio::sync::Mutex;
 
 use crate::{
-    tuforums::level::{Level, get_level, request_random_lvl_id},
+    LogLevel,
+    cache_manager::CacheManager,
+    commands::pagination::{page_buttons, parse_page, total_pages},
+    log_message,
+    tuforums::{
+        clear_info::{LevelPass, get_level_passes},
+        level::{Level, get_level, request_random_lvl_id},
+    },
     utils::get_video_id,
 };
 
-pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(), serenity::Error> {
+pub async fn run(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) -> Result<(), serenity::Error> {
     interaction
         .create_response(
             ctx,
@@ -47,10 +65,14 @@ pub async fn run(ctx: &Context, interaction: &CommandInteraction) -> Result<(),
         }
     };
 
+    let (embed, components) = level_view(level, Some(cache_manager)).await;
+
     interaction
         .edit_response(
             ctx,
-            EditInteractionResponse::new().embed(level_embed(level)),
+            EditInteractionResponse::new()
+                .embed(embed)
+                .components(components),
         )
         .await?;
 
@@ -61,8 +83,53 @@ pub fn register() -> CreateCommand {
     CreateCommand::new("random_lvl").description("Get a random level")
 }
 
-pub fn level_embed(level: Level) -> CreateEmbed {
-    let embed = CreateEmbed::new()
+/// The level embed with its clears button, from the level's passes on TUF.
+pub async fn level_view(
+    level: Level,
+    cache_manager: Option<&Mutex<CacheManager>>,
+) -> (CreateEmbed, Vec<CreateActionRow>) {
+    let passes = match get_level_passes(level.id, cache_manager).await {
+        Ok(passes) => passes,
+        Err(e) => {
+            log_message(
+                format!("Couldn't fetch the clears of level {}: {}", level.id, e).as_str(),
+                LogLevel::Warning,
+            );
+            Vec::new()
+        }
+    };
+
+    let clears_button = CreateButton::new(format!("lvl_clears_open:{}", level.id))
+        .label("All clears")
+        .style(ButtonStyle::Secondary)
+        .disabled(passes.is_empty());
+
+    (
+        level_embed(level, &passes),
+        vec![CreateActionRow::Buttons(vec![clears_button])],
+    )
+}
+
+pub fn level_embed(level: Level, passes: &[LevelPass]) -> CreateEmbed {
+    let highest_score = passes
+        .iter()
+        .map(|pass| pass.score)
+        .max_by(f64::total_cmp)
+        .map_or("-".to_string(), |score| format!("{:.2}", score));
+
+    let highest_speed = passes
+        .iter()
+        .map(|pass| pass.speed)
+        .max_by(f64::total_cmp)
+        .map_or("-".to_string(), |speed| format!("{}x", speed));
+
+    let top_clears = if passes.is_empty() {
+        "No clears yet".to_string()
+    } else {
+        clears_list(&passes[..passes.len().min(5)], 0)
+    };
+
+    CreateEmbed::new()
         .author(
             CreateEmbedAuthor::new(format!(
                 "{} - {} | ID: {}",
@@ -82,13 +149,120 @@ pub fn level_embed(level: Level) -> CreateEmbed {
             format!("{:.2}%", level.highest_acc),
             true,
         )
-        .field("**Highest Score**", "soon", true)
-        .field("**Highest Speed**", "soon", true)
+        .field("**Highest Score**", highest_score, true)
+        .field("**Highest Speed**", highest_speed, true)
         .field("**Download**", format!("[[link]]({})", level.dl_link), true)
+        .field("**Top Clears**", top_clears, false)
         .image(format!(
             "https://i.ytimg.com/vi/{}/maxresdefault.jpg",
             get_video_id(&level.vido_link)
         ))
-        .color(level.difficulty.color);
-    embed
+        .color(level.difficulty.color)
+}
+
+/// One line per clear: position, player, score, X-acc, speed, date and the clear id for `/clear`.
+fn clears_list(passes: &[LevelPass], offset: usize) -> String {
+    passes
+        .iter()
+        .enumerate()
+        .map(|(index, pass)| {
+            format!(
+                "``{:<3}. {:<16} | {:.2} | {:.2}% | {}x | {}`` [#{}]",
+                offset + index + 1,
+                pass.player_name.chars().take(16).collect::<String>(),
+                pass.score,
+                pass.x_acc,
+                pass.speed,
+                pass.date,
+                pass.id
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+const CLEARS_PER_PAGE: u32 = 10;
+
+fn clears_page(level_id: u32, passes: &[LevelPass], page: u32) -> CreateInteractionResponseMessage {
+    let pages = total_pages(passes.len() as u64, CLEARS_PER_PAGE);
+    let page = page.min(pages);
+    let offset = ((page - 1) * CLEARS_PER_PAGE) as usize;
+    let end = (offset + CLEARS_PER_PAGE as usize).min(passes.len());
+
+    let embed = CreateEmbed::new()
+        .title(format!("Clears of level {}", level_id))
+        .url(format!("https://tuforums.com/levels/{}", level_id))
+        .field(
+            "``player | score | xAcc | speed | date`` [clear id]",
+            clears_list(&passes[offset..end], offset),
+            false,
+        )
+        .color(0xFF69B4)
+        .footer(CreateEmbedFooter::new(format!(
+            "{} clears | page {}/{}",
+            passes.len(),
+            page,
+            pages
+        )));
+
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
+        .components(vec![page_buttons(
+            &format!("lvl_clears:{level_id}"),
+            page,
+            pages,
+        )])
+}
+
+/// Opens and pages through the clears browser of a level.
+/// The browser is sent as an ephemeral message, so shared posts like the daily level stay intact.
+pub struct LevelClearsHandler {
+    pub cache_manager: Arc<Mutex<CacheManager>>,
+}
+
+#[async_trait]
+impl EventHandler for LevelClearsHandler {
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let Interaction::Component(component) = interaction else {
+            return;
+        };
+
+        let custom_id = component.data.custom_id.as_str();
+
+        let (level_id, page, open) = match custom_id.split(':').collect::<Vec<_>>().as_slice() {
+            ["lvl_clears_open", level_id] => (level_id.parse::<u32>().ok(), Some(1), true),
+            ["lvl_clears", level_id, ..] => (
+                level_id.parse::<u32>().ok(),
+                parse_page(custom_id, &format!("lvl_clears:{level_id}")),
+                false,
+            ),
+            _ => return,
+        };
+
+        let (Some(level_id), Some(page)) = (level_id, page) else {
+            log_message("Invalid clears button", LogLevel::Warning);
+            return;
+        };
+
+        let response = match get_level_passes(level_id, Some(&self.cache_manager)).await {
+            Ok(passes) if open => CreateInteractionResponse::Message(
+                clears_page(level_id, &passes, page).ephemeral(true),
+            ),
+            Ok(passes) => {
+                CreateInteractionResponse::UpdateMessage(clears_page(level_id, &passes, page))
+            }
+            Err(e) => CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(format!("⚠️ Couldn't fetch the clears: {}", e))
+                    .ephemeral(true),
+            ),
+        };
+
+        if let Err(why) = component.create_response(&ctx.http, response).await {
+            log_message(
+                format!("Failed to answer the clears browser: {:?}", why).as_str(),
+                LogLevel::Warning,
+            );
+        }
+    }
 }
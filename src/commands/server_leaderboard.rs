@@ -0,0 +1,220 @@
+use std::{collections::HashMap, sync::Arc};
+
+use mongodb::bson::doc;
+use serenity::{
+    all::{Context, CreateEmbed, CreateEmbedFooter, GuildId, UserId},
+    futures::{StreamExt, TryStreamExt},
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    LogLevel,
+    cache_manager::CacheManager,
+    database::Database,
+    log_message,
+    tuforums::profile::{Profile, get_profile},
+};
+
+/// How many profiles are fetched from TUF at the same time.
+const CONCURRENT_FETCHES: usize = 8;
+
+/// How many members a `$in` query on the `users` collection asks for at once.
+const LINK_BATCH: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ServerSort {
+    Ranked,
+    General,
+    XAcc,
+    TopDiff,
+}
+
+impl ServerSort {
+    pub fn from_id(id: &str) -> Option<ServerSort> {
+        match id {
+            "ranked" => Some(ServerSort::Ranked),
+            "general" => Some(ServerSort::General),
+            "xacc" => Some(ServerSort::XAcc),
+            "topdiff" => Some(ServerSort::TopDiff),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ServerSort::Ranked => "Ranked score",
+            ServerSort::General => "General score",
+            ServerSort::XAcc => "X-Accuracy",
+            ServerSort::TopDiff => "Top difficulty",
+        }
+    }
+
+    fn value(&self, profile: &Profile) -> f64 {
+        match self {
+            ServerSort::Ranked => profile.stats.ranked_score,
+            ServerSort::General => profile.stats.general_score,
+            ServerSort::XAcc => profile.stats.avg_xacc,
+            // Special tiers sort below the PGU scale, then ranked score breaks ties below.
+            ServerSort::TopDiff => profile.stats.top_diff.order().map(f64::from).unwrap_or(-1.),
+        }
+    }
+}
+
+pub struct ServerEntry {
+    pub user_id: UserId,
+    pub profile_id: u64,
+    pub profile: Profile,
+}
+
+/// The guild's members who linked a TUF profile, as `(discord id, profile id)`.
+pub async fn linked_members(
+    ctx: &Context,
+    guild_id: GuildId,
+    database: &Database,
+) -> Result<Vec<(UserId, u64)>, Box<dyn std::error::Error + Send + Sync>> {
+    let member_ids = guild_id
+        .members_iter(&ctx.http)
+        .map_ok(|member| member.user.id)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    linked_profiles(&member_ids, database).await
+}
+
+/// The users among `user_ids` who linked a TUF profile, as `(discord id, profile id)`.
+pub async fn linked_profiles(
+    user_ids: &[UserId],
+    database: &Database,
+) -> Result<Vec<(UserId, u64)>, Box<dyn std::error::Error + Send + Sync>> {
+    let collection = database
+        .get_collection("onuzglorp-bot", "users")
+        .await
+        .ok_or("Database is not connected")?;
+
+    let user_ids = user_ids
+        .iter()
+        .map(|user_id| user_id.get() as i64)
+        .collect::<Vec<_>>();
+
+    let mut linked = Vec::new();
+    for batch in user_ids.chunks(LINK_BATCH) {
+        let users = collection
+            .find(doc! { "_id": { "$in": batch } })
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        linked.extend(users.iter().filter_map(|user| {
+            Some((
+                UserId::new(user.get_i64("_id").ok()? as u64),
+                user.get_i64("profile_id").ok()? as u64,
+            ))
+        }));
+    }
+
+    Ok(linked)
+}
+
+/// Ranks the guild's linked members. Profiles go through the cache, so repeated
+/// lookups in a short time don't hit TUF again.
+pub async fn get_server_standings(
+    ctx: &Context,
+    guild_id: GuildId,
+    database: &Database,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+    sort: ServerSort,
+) -> Result<Vec<ServerEntry>, Box<dyn std::error::Error + Send + Sync>> {
+    let members = linked_members(ctx, guild_id, database).await?;
+
+    // Several Discord accounts can link the same profile, it only shows up once.
+    let mut by_profile = HashMap::new();
+    for (user_id, profile_id) in members {
+        by_profile.entry(profile_id).or_insert(user_id);
+    }
+
+    let mut standings = serenity::futures::stream::iter(by_profile)
+        .map(|(profile_id, user_id)| async move {
+            match get_profile(profile_id, Some(cache_manager)).await {
+                Ok((profile, _)) => Some(ServerEntry {
+                    user_id,
+                    profile_id,
+                    profile,
+                }),
+                Err(e) => {
+                    log_message(
+                        format!("Couldn't fetch profile {profile_id}: {e}").as_str(),
+                        LogLevel::Warning,
+                    );
+                    None
+                }
+            }
+        })
+        .buffer_unordered(CONCURRENT_FETCHES)
+        .filter_map(|entry| async move { entry })
+        .collect::<Vec<_>>()
+        .await;
+
+    standings.sort_by(|a, b| {
+        sort.value(&b.profile)
+            .total_cmp(&sort.value(&a.profile))
+            .then(
+                b.profile
+                    .stats
+                    .ranked_score
+                    .total_cmp(&a.profile.stats.ranked_score),
+            )
+    });
+
+    Ok(standings)
+}
+
+pub fn server_embed(
+    title: &str,
+    standings: &[ServerEntry],
+    sort: ServerSort,
+    limit: usize,
+    highlight: Option<UserId>,
+) -> CreateEmbed {
+    let lines = standings
+        .iter()
+        .enumerate()
+        .filter(|(index, entry)| *index < limit || Some(entry.user_id) == highlight)
+        .map(|(index, entry)| {
+            let stats = &entry.profile.stats;
+            let value = match sort {
+                ServerSort::Ranked => format!("{:.2}", stats.ranked_score),
+                ServerSort::General => format!("{:.2}", stats.general_score),
+                ServerSort::XAcc => format!("{:.2}%", stats.avg_xacc * 100.),
+                ServerSort::TopDiff => stats.top_diff.name.clone(),
+            };
+            let line = format!(
+                "``{:<3}. {:<20}[{}] | {}`` <@{}>",
+                index + 1,
+                entry.profile.name,
+                entry.profile_id,
+                value,
+                entry.user_id
+            );
+
+            if Some(entry.user_id) == highlight {
+                format!("📍 **{}**", line)
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>();
+
+    CreateEmbed::new()
+        .title(title)
+        .description(if lines.is_empty() {
+            "Nobody here has linked a profile yet, use `/link`.".to_string()
+        } else {
+            lines.join("\n")
+        })
+        .color(0xFF69B4)
+        .footer(CreateEmbedFooter::new(format!(
+            "{} linked members | sorted by {}",
+            standings.len(),
+            sort.label()
+        )))
+}
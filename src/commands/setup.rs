@@ -1,22 +1,123 @@
-use std::{time::Duration, vec};
+use std::{sync::Arc, time::Duration, vec};
 
 use mongodb::{
     Collection,
     bson::{Document, doc},
 };
 use serenity::all::{
-    ChannelId, CommandInteraction, ComponentInteraction, ComponentInteractionDataKind, Context,
-    CreateCommand, CreateInteractionResponse, CreateInteractionResponseMessage, CreateQuickModal,
+    ButtonStyle, ChannelId, CommandInteraction, ComponentInteraction, ComponentInteractionDataKind,
+    Context, CreateActionRow, CreateAllowedMentions, CreateButton, CreateCommand, CreateInputText,
+    CreateInteractionResponse, CreateInteractionResponseMessage, CreateQuickModal,
     CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, EditInteractionResponse,
-    Permissions,
+    InputTextStyle, Permissions, RoleId,
 };
+use tokio::sync::Mutex;
 
-use crate::database::Database;
+use crate::{
+    LogLevel,
+    cache_manager::CacheManager,
+    database::Database,
+    log_message,
+    tasks::role_sync::{RoleRule, apply, guild_rules, plan_guild},
+    tuforums::difficulty::DifficultyRange,
+};
+
+/// A channel the bot posts something to, stored in the `setups` collection under `setup_type`.
+pub struct ChannelSetup {
+    /// Value of the option in the setup menu.
+    pub value: &'static str,
+    pub setup_type: &'static str,
+    pub label: &'static str,
+    pub description: &'static str,
+    /// Posted in the channel once it's setup.
+    pub announcement: &'static str,
+    /// Also asks for a difficulty range like `G1-U20`, stored as `min_diff` and `max_diff`.
+    pub difficulty_range: bool,
+}
+
+pub const CHANNEL_SETUPS: [ChannelSetup; 7] = [
+    ChannelSetup {
+        value: "rand-lvl-channel",
+        setup_type: "daily-random-lvl-channel",
+        label: "Daily Random LVL channel",
+        description: "Select the channel where the daily random level will be posted",
+        announcement: "⚠️ **Random levels will be posted here every day at midnight UTC** ⚠️",
+        difficulty_range: false,
+    },
+    ChannelSetup {
+        value: "weekly-digest-channel",
+        setup_type: "weekly-digest-channel",
+        label: "Weekly server digest channel",
+        description: "Select the channel where the server leaderboard will be posted every week",
+        announcement: "⚠️ **The server leaderboard will be posted here every Monday at midnight UTC** ⚠️",
+        difficulty_range: false,
+    },
+    ChannelSetup {
+        value: "rank-updates-channel",
+        setup_type: "rank-updates-channel",
+        label: "Rank updates channel",
+        description: "Select the channel where linked members' rank changes will be posted",
+        announcement: "⚠️ **Rank changes of linked members will be posted here, use `/notify` to opt out** ⚠️",
+        difficulty_range: false,
+    },
+    ChannelSetup {
+        value: "pass-feed-channel",
+        setup_type: "pass-feed-channel",
+        label: "New pass feed channel",
+        description: "Select the channel where linked members' new passes will be posted",
+        announcement: "⚠️ **New passes of linked members will be posted here, use `/notify` to opt out** ⚠️",
+        difficulty_range: false,
+    },
+    ChannelSetup {
+        value: "wf-feed-channel",
+        setup_type: "wf-feed-channel",
+        label: "World's first feed channel",
+        description: "Select the channel where new world's firsts on TUF will be posted",
+        announcement: "⚠️ **New world's firsts on TUF will be posted here** ⚠️",
+        difficulty_range: true,
+    },
+    ChannelSetup {
+        value: "new-level-feed-channel",
+        setup_type: "new-level-feed-channel",
+        label: "New level feed channel",
+        description: "Select the channel where new and newly rated levels will be posted",
+        announcement: "⚠️ **New and newly rated levels on TUF will be posted here** ⚠️",
+        difficulty_range: true,
+    },
+    ChannelSetup {
+        value: "rerate-feed-channel",
+        setup_type: "rerate-feed-channel",
+        label: "Rerate feed channel",
+        description: "Select the channel where level rerates will be posted",
+        announcement: "⚠️ **Level rerates on TUF will be posted here** ⚠️",
+        difficulty_range: true,
+    },
+];
+
+/// Menu options for the role sync rules, next to the [`CHANNEL_SETUPS`].
+const ROLE_SETUPS: [(&str, &str, &str); 3] = [
+    (
+        "role-rule-add",
+        "Add a role rule",
+        "Give a role to linked members by top difficulty or rank",
+    ),
+    (
+        "role-rule-remove",
+        "Remove role rules",
+        "Stop syncing a role",
+    ),
+    (
+        "role-sync-preview",
+        "Preview role sync",
+        "Show the role changes a sync would make, then apply them",
+    ),
+];
 
 pub async fn run(
     ctx: &Context,
     interaction: &CommandInteraction,
     database: &Database,
+    cache_manager: &Arc<Mutex<CacheManager>>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let user_id = interaction.user.id;
 
@@ -29,15 +130,15 @@ pub async fn run(
                     .select_menu(CreateSelectMenu::new(
                         "setup-menu",
                         CreateSelectMenuKind::String {
-                            options: vec![
-                            CreateSelectMenuOption::new(
-                                "Daily Random LVL channel",
-                                "rand-lvl-channel",
-                            )
-                            .description(
-                                "Select the channel where the daily random level will be posted",
-                            ),
-                        ],
+                            options: CHANNEL_SETUPS
+                                .iter()
+                                .map(|setup| (setup.value, setup.label, setup.description))
+                                .chain(ROLE_SETUPS)
+                                .map(|(value, label, description)| {
+                                    CreateSelectMenuOption::new(label, value)
+                                        .description(description)
+                                })
+                                .collect(),
                         },
                     )),
             ),
@@ -61,20 +162,23 @@ pub async fn run(
                     _ => panic!("unexpected interaction data kind"),
                 };
 
-                match selected.as_str() {
-                    "rand-lvl-channel" => {
+                match CHANNEL_SETUPS
+                    .iter()
+                    .find(|setup| setup.value == selected.as_str())
+                {
+                    Some(setup) => {
                         let collection = database
                             .get_collection("onuzglorp-bot", "setups")
                             .await
                             .expect("Failed to get collection");
                         let guild_id = comp_interaction.guild_id.unwrap();
 
-                        if collection.find_one(doc! {"guild_id": &guild_id.get().to_string(), "type": "daily-random-lvl-channel"}).await.unwrap().is_some() {
+                        if collection.find_one(doc! {"guild_id": &guild_id.get().to_string(), "type": setup.setup_type}).await.unwrap().is_some() {
                             interaction
                                 .edit_response(
                                     ctx,
                                     EditInteractionResponse::new()
-                                        .content("❌ Daily random level channel already setup")
+                                        .content(format!("❌ {} already setup", setup.label))
                                         .components(vec![]),
                                 )
                                 .await
@@ -82,9 +186,34 @@ pub async fn run(
                             return Ok(());
                         }
 
-                        setup_random_lvl_channel(ctx, &comp_interaction, &collection).await;
+                        setup_channel(ctx, &comp_interaction, &collection, setup).await;
+                    }
+                    None if selected.starts_with("role-") => {
+                        let collection = database
+                            .get_collection("onuzglorp-bot", "role_rules")
+                            .await
+                            .expect("Failed to get collection");
+
+                        match selected.as_str() {
+                            "role-rule-add" => {
+                                add_role_rule(ctx, &comp_interaction, &collection).await
+                            }
+                            "role-rule-remove" => {
+                                remove_role_rules(ctx, &comp_interaction, &collection).await
+                            }
+                            _ => {
+                                preview_role_sync(
+                                    ctx,
+                                    interaction,
+                                    &comp_interaction,
+                                    database,
+                                    cache_manager,
+                                )
+                                .await
+                            }
+                        }
                     }
-                    _ => {
+                    None => {
                         comp_interaction
                             .edit_response(
                                 ctx,
@@ -114,23 +243,30 @@ pub async fn run(
     Ok(())
 }
 
-pub async fn setup_random_lvl_channel(
+pub async fn setup_channel(
     ctx: &Context,
     interaction: &ComponentInteraction,
     collection: &Collection<Document>,
+    setup: &ChannelSetup,
 ) {
     let guild_id = interaction.guild_id.unwrap();
     let user_id = interaction.user.id;
 
-    let response = interaction
-        .quick_modal(
-            ctx,
-            CreateQuickModal::new("Text Channel Setup")
-                .timeout(Duration::from_secs(60))
-                .short_field("channel id"),
-        )
-        .await
-        .unwrap();
+    let mut modal = CreateQuickModal::new("Text Channel Setup")
+        .timeout(Duration::from_secs(60))
+        .short_field("channel id");
+    if setup.difficulty_range {
+        modal = modal.field(
+            CreateInputText::new(
+                InputTextStyle::Short,
+                "difficulty range, like G1-U20 (empty for all)",
+                "",
+            )
+            .required(false),
+        );
+    }
+
+    let response = interaction.quick_modal(ctx, modal).await.unwrap();
 
     match response {
         Some(modal_interaction) => {
@@ -206,28 +342,68 @@ pub async fn setup_random_lvl_channel(
                 return;
             }
 
-            if collection.find_one(doc! {"guild_id": guild_id.get().to_string(), "type": "daily-random-lvl-channel"}).await.unwrap().is_some() {
-                    modal_interaction.interaction
-                        .create_response(
-                            ctx,
-                            CreateInteractionResponse::UpdateMessage(
-                                CreateInteractionResponseMessage::new()
-                                    .content("❌ Daily random level channel already setup")
-                                    .components(vec![]),
-                            ),
-                        )
-                        .await
-                        .unwrap();
-                    return;
-                }
+            if collection
+                .find_one(doc! {"guild_id": guild_id.get().to_string(), "type": setup.setup_type})
+                .await
+                .unwrap()
+                .is_some()
+            {
+                modal_interaction
+                    .interaction
+                    .create_response(
+                        ctx,
+                        CreateInteractionResponse::UpdateMessage(
+                            CreateInteractionResponseMessage::new()
+                                .content(format!("❌ {} already setup", setup.label))
+                                .components(vec![]),
+                        ),
+                    )
+                    .await
+                    .unwrap();
+                return;
+            }
+
+            let mut setup_doc = doc! {
+                "guild_id": guild_id.get().to_string(),
+                "channel_id": channel_input,
+                "type": setup.setup_type,
+                "setup_by": user_id.to_string(),
+            };
+
+            let range_input = modal_interaction
+                .inputs
+                .get(1)
+                .map(|range| range.trim().to_string())
+                .unwrap_or_default();
+            if !range_input.is_empty() {
+                let range = match range_input.parse::<DifficultyRange>() {
+                    Ok(range) => range,
+                    Err(message) => {
+                        modal_interaction
+                            .interaction
+                            .create_response(
+                                ctx,
+                                CreateInteractionResponse::UpdateMessage(
+                                    CreateInteractionResponseMessage::new()
+                                        .content(format!(
+                                            "❌ Invalid difficulty range: {}\nUse something like `G1-U20`",
+                                            message
+                                        ))
+                                        .components(vec![]),
+                                ),
+                            )
+                            .await
+                            .unwrap();
+                        return;
+                    }
+                };
+
+                setup_doc.insert("min_diff", range.min.to_string());
+                setup_doc.insert("max_diff", range.max.to_string());
+            }
 
             collection
-                .insert_one(doc! {
-                    "guild_id": guild_id.get().to_string(),
-                    "channel_id": channel_input,
-                    "type": "daily-random-lvl-channel",
-                    "setup_by": user_id.to_string(),
-                })
+                .insert_one(setup_doc)
                 .await
                 .expect("Failed to insert document");
 
@@ -237,10 +413,7 @@ pub async fn setup_random_lvl_channel(
                     ctx,
                     CreateInteractionResponse::UpdateMessage(
                         CreateInteractionResponseMessage::new()
-                            .content(format!(
-                                "✅ Daily random level channel setup in <#{}>",
-                                channel_input
-                            ))
+                            .content(format!("✅ {} setup in <#{}>", setup.label, channel_input))
                             .components(vec![]),
                     ),
                 )
@@ -256,10 +429,7 @@ pub async fn setup_random_lvl_channel(
             channel
                 .guild()
                 .unwrap()
-                .say(
-                    ctx,
-                    "⚠️ **Random levels will be posted here every day at midnight UTC** ⚠️",
-                )
+                .say(ctx, setup.announcement)
                 .await
                 .unwrap();
         }
@@ -279,6 +449,298 @@ pub async fn setup_random_lvl_channel(
     }
 }
 
+/// Replaces the setup message with `content` and no components.
+fn update_message(content: String) -> CreateInteractionResponse {
+    CreateInteractionResponse::UpdateMessage(
+        CreateInteractionResponseMessage::new()
+            .content(content)
+            .components(vec![])
+            .allowed_mentions(CreateAllowedMentions::new()),
+    )
+}
+
+async fn add_role_rule(
+    ctx: &Context,
+    interaction: &ComponentInteraction,
+    collection: &Collection<Document>,
+) {
+    let guild_id = interaction.guild_id.unwrap();
+
+    let response = interaction
+        .quick_modal(
+            ctx,
+            CreateQuickModal::new("Role Rule Setup")
+                .timeout(Duration::from_secs(60))
+                .short_field("role id")
+                .short_field("rule, like diff:U1-U20 or rank:1-100"),
+        )
+        .await
+        .unwrap();
+
+    let Some(modal_interaction) = response else {
+        interaction
+            .create_response(ctx, update_message("❌ Setup timed out".to_string()))
+            .await
+            .unwrap();
+        return;
+    };
+
+    let Ok(role_id) = modal_interaction.inputs[0].trim().parse::<u64>() else {
+        modal_interaction
+            .interaction
+            .create_response(ctx, update_message("❌ Invalid role ID format".to_string()))
+            .await
+            .unwrap();
+        return;
+    };
+    let role_id = RoleId::new(role_id);
+
+    let roles = guild_id.roles(&ctx.http).await.unwrap_or_default();
+    if !roles.contains_key(&role_id) {
+        modal_interaction
+            .interaction
+            .create_response(
+                ctx,
+                update_message("❌ Role not found in this server".to_string()),
+            )
+            .await
+            .unwrap();
+        return;
+    }
+
+    let Some(rule) = RoleRule::parse(role_id, &modal_interaction.inputs[1]) else {
+        modal_interaction
+            .interaction
+            .create_response(
+                ctx,
+                update_message(
+                    "❌ Invalid rule, use something like `diff:U1-U20` or `rank:1-100`".to_string(),
+                ),
+            )
+            .await
+            .unwrap();
+        return;
+    };
+
+    collection
+        .insert_one(doc! {
+            "guild_id": guild_id.get().to_string(),
+            "role_id": role_id.get().to_string(),
+            "rule": &rule.text,
+            "setup_by": interaction.user.id.to_string(),
+        })
+        .await
+        .expect("Failed to insert document");
+
+    modal_interaction.interaction
+        .create_response(ctx, update_message(format!(
+            "✅ <@&{}> will be synced for linked members matching `{}`\nUse **Preview role sync** to check it before the next sync.",
+            role_id, rule.text
+        )))
+        .await
+        .unwrap();
+}
+
+async fn remove_role_rules(
+    ctx: &Context,
+    interaction: &ComponentInteraction,
+    collection: &Collection<Document>,
+) {
+    let guild_id = interaction.guild_id.unwrap();
+
+    let response = interaction
+        .quick_modal(
+            ctx,
+            CreateQuickModal::new("Remove Role Rules")
+                .timeout(Duration::from_secs(60))
+                .short_field("role id"),
+        )
+        .await
+        .unwrap();
+
+    let Some(modal_interaction) = response else {
+        interaction
+            .create_response(ctx, update_message("❌ Setup timed out".to_string()))
+            .await
+            .unwrap();
+        return;
+    };
+
+    let role_id = modal_interaction.inputs[0].trim();
+    let deleted = collection
+        .delete_many(doc! { "guild_id": guild_id.get().to_string(), "role_id": role_id })
+        .await
+        .map(|result| result.deleted_count)
+        .unwrap_or(0);
+
+    modal_interaction
+        .interaction
+        .create_response(
+            ctx,
+            update_message(if deleted == 0 {
+                format!("❌ No rule gives <@&{}>", role_id)
+            } else {
+                format!(
+                    "✅ Removed {} rule(s) for <@&{}>, members keep the role until removed by hand",
+                    deleted, role_id
+                )
+            }),
+        )
+        .await
+        .unwrap();
+}
+
+/// Discord's limit on a message's content.
+const MAX_CONTENT_LEN: usize = 2000;
+
+/// Room for the rules in the role sync preview, the changes get the rest.
+const MAX_RULES_LEN: usize = 900;
+
+/// Keeps `lines` under Discord's message length, counting what was left out.
+fn fit_lines(lines: &[String], max_len: usize) -> String {
+    let mut text = String::new();
+    for (index, line) in lines.iter().enumerate() {
+        if text.len() + line.len() + 30 > max_len {
+            text.push_str(&format!("...and {} more", lines.len() - index));
+            break;
+        }
+        text.push_str(line);
+        text.push('\n');
+    }
+    text
+}
+
+/// Dry run of the role sync, with a button applying it.
+async fn preview_role_sync(
+    ctx: &Context,
+    command: &CommandInteraction,
+    interaction: &ComponentInteraction,
+    database: &Database,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) {
+    let guild_id = interaction.guild_id.unwrap();
+
+    interaction
+        .create_response(ctx, update_message("🔎 Checking members...".to_string()))
+        .await
+        .unwrap();
+
+    let rules = guild_rules(guild_id, database).await.unwrap_or_default();
+    if rules.is_empty() {
+        command
+            .edit_response(
+                ctx,
+                EditInteractionResponse::new().content("❌ No role rules setup yet"),
+            )
+            .await
+            .unwrap();
+        return;
+    }
+
+    let plan = match plan_guild(ctx, guild_id, database, cache_manager).await {
+        Ok(plan) => plan,
+        Err(e) => {
+            command
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new()
+                        .content(format!("❌ Couldn't check the members: {}", e)),
+                )
+                .await
+                .unwrap();
+            return;
+        }
+    };
+
+    let header = "**Rules**\n";
+    let note = "-# Members without a linked profile lose every role in these rules, including ones given by hand.\n\n**Changes**\n";
+    let rules = fit_lines(
+        &rules
+            .iter()
+            .map(|rule| format!("<@&{}> `{}`", rule.role_id, rule.text))
+            .collect::<Vec<_>>(),
+        MAX_RULES_LEN,
+    );
+    let changes = plan.describe();
+    let changes = if changes.is_empty() {
+        "Everyone already has the right roles".to_string()
+    } else {
+        fit_lines(
+            &changes,
+            MAX_CONTENT_LEN - header.len() - rules.len() - note.len(),
+        )
+    };
+    let content = format!("{header}{rules}{note}{changes}");
+
+    if let Err(e) = command
+        .edit_response(
+            ctx,
+            EditInteractionResponse::new()
+                .content(content)
+                .allowed_mentions(CreateAllowedMentions::new())
+                .components(if plan.changes.is_empty() {
+                    vec![]
+                } else {
+                    vec![CreateActionRow::Buttons(vec![
+                        CreateButton::new("role-sync-apply")
+                            .label(format!("Apply {} changes", plan.changes.len()))
+                            .style(ButtonStyle::Danger),
+                    ])]
+                }),
+        )
+        .await
+    {
+        log_message(
+            format!("Failed to show the role sync preview: {}", e).as_str(),
+            LogLevel::Error,
+        );
+        return;
+    }
+
+    if plan.changes.is_empty() {
+        return;
+    }
+
+    let response = command.get_response(ctx).await.unwrap();
+    let Some(button) = response
+        .await_component_interaction(&ctx.shard)
+        .timeout(Duration::from_secs(60))
+        .author_id(command.user.id)
+        .await
+    else {
+        command
+            .edit_response(ctx, EditInteractionResponse::new().components(vec![]))
+            .await
+            .unwrap();
+        return;
+    };
+
+    button
+        .create_response(ctx, update_message("⏳ Syncing roles...".to_string()))
+        .await
+        .unwrap();
+
+    let failures = apply(ctx, guild_id, &plan).await;
+    command
+        .edit_response(
+            ctx,
+            EditInteractionResponse::new()
+                .content(if failures.is_empty() {
+                    format!("✅ Applied {} role changes", plan.changes.len())
+                } else {
+                    format!(
+                        "⚠️ Applied {} of {} role changes\n{}",
+                        plan.changes.len() - failures.len(),
+                        plan.changes.len(),
+                        fit_lines(&failures, 1800)
+                    )
+                })
+                .allowed_mentions(CreateAllowedMentions::new()),
+        )
+        .await
+        .unwrap();
+}
+
 pub fn register() -> CreateCommand {
     CreateCommand::new("setup")
         .description("Setup the bot")
@@ -1,5 +1,6 @@
 use mongodb::{Client, bson::Document};
 
+#[derive(Clone)]
 pub struct Database {
     pub client: Option<Client>,
 }
@@ -0,0 +1,479 @@
+use crate::tuforums::clear_info::Judgements;
+
+pub mod scoring_model;
+
+use scoring_model::ScoringModel;
+
+/// X-accuracy as a fraction, no judgements at all counts as 0.
+pub fn acc_by_judgement(
+    Judgements(
+        early_double,
+        early_single,
+        e_perfect,
+        perfect,
+        l_perfect,
+        late_single,
+        late_double,
+    ): Judgements,
+) -> f64 {
+    // Summed as floats so huge inputs can't overflow.
+    let judgement_count = [
+        early_double,
+        early_single,
+        e_perfect,
+        perfect,
+        l_perfect,
+        late_single,
+        late_double,
+    ]
+    .iter()
+    .map(|count| *count as f64)
+    .sum::<f64>();
+
+    if judgement_count == 0. {
+        return 0.;
+    }
+
+    (perfect as f64
+        + (e_perfect as f64 + l_perfect as f64) * 0.75
+        + (early_single as f64 + late_single as f64) * 0.4
+        + (early_double as f64 + late_double as f64) * 0.2)
+        / judgement_count
+}
+
+/// How close to 100% still counts as a pure perfect, so float noise doesn't cost the 10x bonus.
+const PURE_PERFECT_EPSILON: f64 = 1e-9;
+
+/// Flat below 95%, a curve up to ~5.5 just under 100%, and TUF's 10x pure perfect bonus at 100%.
+/// The jump at 100% is intended; NaN counts as no bonus.
+pub fn x_acc_multiplier(x_acc: f64) -> f64 {
+    match x_acc {
+        x if x.is_nan() || x < 95. => 1.0,
+        x if x < 100. - PURE_PERFECT_EPSILON => -0.027 / (x / 100. - 1.0054) + 0.513,
+        _ => 10.,
+    }
+}
+
+/// Speeds under 1.0x aren't ranked on TUF, so they're worth nothing, and so is NaN.
+pub fn speed_multiplier(speed: f64) -> f64 {
+    match speed {
+        speed if speed.is_nan() || speed < 1.0 => 0.0,
+        speed if speed < 1.1 => -3.5 * (speed - 1.0) + 1.0,
+        speed if speed < 1.5 => 0.65,
+        speed if speed < 2.0 => 0.7 * (speed - 1.5) + 0.65,
+        _ => 1.0,
+    }
+}
+
+/// Misses that `score_v2` forgives before it starts penalising.
+pub fn allowed_misses(tile_count: u32) -> u32 {
+    tile_count / 315
+}
+
+/// The multiplier `score_v2` applies for the given misses.
+pub fn miss_penalty(misses: u32, tile_count: u32) -> f64 {
+    let am = (misses as f64 - allowed_misses(tile_count) as f64).max(0.0);
+
+    // The powers are only taken inside their own range, where the base can't go negative.
+    if am <= 0.0 {
+        1.0
+    } else if am <= 1.0 {
+        0.9
+    } else if am <= 25.5 {
+        0.9 - ((am - 1.0) / 24.5).powf(0.7) * 0.2
+    } else if am <= 50.0 {
+        1.0 + ((50.0 - am) / 24.5).powf(0.7) * 0.2 - 0.5
+    } else {
+        0.5
+    }
+}
+
+/// Every step of the score calculation, so the embeds can explain where a score came from.
+#[derive(Debug, Clone)]
+pub struct ScoreBreakdown {
+    pub model: &'static str,
+    pub base_score: f64,
+    pub x_acc: f64,
+    pub speed: f64,
+    pub misses: u32,
+    pub tile_count: u32,
+    pub x_acc_multiplier: f64,
+    pub speed_multiplier: f64,
+    pub no_miss_bonus: f64,
+    pub allowed_misses: u32,
+    pub miss_penalty: f64,
+    pub final_score: f64,
+}
+
+/// Renders a score breakdown as a spoiler, so it stays collapsed until the user clicks it.
+pub fn breakdown_field(breakdown: &ScoreBreakdown) -> String {
+    format!(
+        "||``model: {}``\n``base score: {}``\n``xAcc multiplier: x{:.4}`` ({:.2}%)\n``speed multiplier: x{:.4}`` ({}x)\n``no miss bonus: x{}``\n``miss penalty: x{:.4}`` ({} misses, {} allowed for {} tiles)\n``final score: {:.2}``||",
+        breakdown.model,
+        breakdown.base_score,
+        breakdown.x_acc_multiplier,
+        breakdown.x_acc,
+        breakdown.speed_multiplier,
+        breakdown.speed,
+        breakdown.no_miss_bonus,
+        breakdown.miss_penalty,
+        breakdown.misses,
+        breakdown.allowed_misses,
+        breakdown.tile_count,
+        breakdown.final_score,
+    )
+}
+
+/// How much a pass counts towards ranked score at the given position, passes from #20 on don't count.
+pub fn ranked_weight(position: u32) -> f64 {
+    if position >= 20 {
+        0.0
+    } else {
+        0.9f64.powi(position.max(1) as i32 - 1)
+    }
+}
+
+/// Ranked score of a player from the scores of their best pass on each level.
+pub fn ranked_score(scores: &[f64]) -> f64 {
+    let mut scores = scores.to_vec();
+    scores.sort_by(|a, b| b.total_cmp(a));
+    scores
+        .iter()
+        .enumerate()
+        .map(|(index, score)| score * ranked_weight(index as u32 + 1))
+        .sum()
+}
+
+/// Lowest score a new pass needs to raise the ranked score by `gain`.
+/// `others` are the best scores on every other level, `existing` the best score on the
+/// level being played, which only stops counting once the new pass beats it.
+pub fn min_score_for_gain(others: &[f64], existing: Option<f64>, gain: f64) -> f64 {
+    let ranked_with = |score: Option<f64>| {
+        let mut scores = others.to_vec();
+        scores.extend(score);
+        ranked_score(&scores)
+    };
+    let current = ranked_with(existing);
+    let gain_at = |score: f64| ranked_with(Some(score.max(existing.unwrap_or(0.)))) - current;
+
+    let low = existing.unwrap_or(0.);
+    if gain.is_nan() || gain <= 0. || gain_at(low) >= gain {
+        return low;
+    }
+
+    // A pass worth `current + gain` lands first with weight 1, so it always reaches the gain.
+    let (mut low, mut high) = (low, current + gain);
+    for _ in 0..64 {
+        let mid = (low + high) / 2.;
+        if gain_at(mid) >= gain {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+
+    high
+}
+
+/// Outcome of solving a score formula backwards for a target score.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TargetSolution<T> {
+    /// The target is reached no matter the value.
+    Always,
+    /// The tightest value that still reaches the target.
+    Reached(T),
+    /// The target can't be reached; holds the best score that can be.
+    Unreachable(f64),
+}
+
+/// Lowest X-accuracy (in %) that reaches `target` with the given misses and speed.
+pub fn min_x_acc_for_score(
+    model: &dyn ScoringModel,
+    base_score: f64,
+    target: f64,
+    tile_count: u32,
+    misses: u32,
+    speed: f64,
+) -> TargetSolution<f64> {
+    let score_at = |x_acc: f64| model.score(base_score, x_acc, tile_count, misses, speed);
+
+    let best = score_at(100.);
+    if best < target {
+        return TargetSolution::Unreachable(best);
+    }
+    // The multiplier is flat below 95%, so anything from there on counts as "any accuracy".
+    if score_at(95.) >= target {
+        return TargetSolution::Always;
+    }
+
+    let (mut low, mut high) = (95., 100.);
+    for _ in 0..64 {
+        let mid = (low + high) / 2.;
+        if score_at(mid) >= target {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+
+    TargetSolution::Reached(high)
+}
+
+/// Most misses allowed while still reaching `target` with the given X-accuracy and speed.
+pub fn max_misses_for_score(
+    model: &dyn ScoringModel,
+    base_score: f64,
+    target: f64,
+    tile_count: u32,
+    x_acc: f64,
+    speed: f64,
+) -> TargetSolution<u32> {
+    let score_at = |misses: u32| model.score(base_score, x_acc, tile_count, misses, speed);
+
+    let best = score_at(0);
+    if best < target {
+        return TargetSolution::Unreachable(best);
+    }
+    // Past 50 misses over the allowance the v2 penalty stays at 0.5, and older models don't penalise at all.
+    let floor = model.allowed_misses(tile_count) + 51;
+    if score_at(floor) >= target {
+        return TargetSolution::Always;
+    }
+
+    let (mut low, mut high) = (0, floor);
+    while high - low > 1 {
+        let mid = (low + high) / 2;
+        if score_at(mid) >= target {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    TargetSolution::Reached(low)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{
+        TargetSolution, acc_by_judgement, max_misses_for_score, min_score_for_gain,
+        min_x_acc_for_score, miss_penalty, ranked_score,
+        scoring_model::{NoMissPenalty, ScoreV2, ScoringModel},
+        speed_multiplier, x_acc_multiplier,
+    };
+    use crate::tuforums::clear_info::Judgements;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    // Reference values worked out by hand from the published score v2 formula. They check the
+    // code against the formula, not against TUF: no pass from the API has been checked here yet.
+
+    #[test]
+    fn x_acc_multiplier_golden() {
+        assert_close(x_acc_multiplier(0.), 1.0);
+        assert_close(x_acc_multiplier(94.99), 1.0);
+        assert_close(x_acc_multiplier(95.), 1.000364620938);
+        assert_close(x_acc_multiplier(98.), 1.575992125984);
+        assert_close(x_acc_multiplier(99.), 2.266246753247);
+        assert_close(x_acc_multiplier(99.5), 3.109153846154);
+        assert_close(x_acc_multiplier(100.), 10.);
+    }
+
+    #[test]
+    fn x_acc_multiplier_pure_perfect_survives_float_noise() {
+        assert_close(x_acc_multiplier(100. - 1e-12), 10.);
+        assert_close(x_acc_multiplier(100.5), 10.);
+        assert_close(x_acc_multiplier(f64::NAN), 1.0);
+    }
+
+    #[test]
+    fn speed_multiplier_golden() {
+        assert_close(speed_multiplier(1.0), 1.0);
+        assert_close(speed_multiplier(1.05), 0.825);
+        assert_close(speed_multiplier(1.2), 0.65);
+        assert_close(speed_multiplier(1.75), 0.825);
+        assert_close(speed_multiplier(2.0), 1.0);
+        assert_close(speed_multiplier(3.0), 1.0);
+    }
+
+    #[test]
+    fn speed_multiplier_below_one_is_unranked() {
+        assert_close(speed_multiplier(0.5), 0.);
+        assert_close(speed_multiplier(0.), 0.);
+        assert_close(speed_multiplier(-1.), 0.);
+        assert_close(speed_multiplier(f64::NAN), 0.);
+    }
+
+    #[test]
+    fn miss_penalty_golden() {
+        assert_close(miss_penalty(0, 0), 1.0);
+        assert_close(miss_penalty(2, 630), 1.0);
+        assert_close(miss_penalty(3, 630), 0.9);
+        assert_close(miss_penalty(10, 0), 0.800783593532);
+        assert_close(miss_penalty(10, 630), 0.816788688959);
+        assert_close(miss_penalty(26, 0), 0.697134032518);
+        assert_close(miss_penalty(30, 0), 0.673514084671);
+        assert_close(miss_penalty(51, 0), 0.5);
+        assert_close(miss_penalty(u32::MAX, 0), 0.5);
+    }
+
+    #[test]
+    fn acc_by_judgement_golden() {
+        assert_close(acc_by_judgement(Judgements(0, 0, 0, 100, 0, 0, 0)), 1.0);
+        assert_close(acc_by_judgement(Judgements(0, 0, 10, 80, 10, 0, 0)), 0.95);
+        assert_close(acc_by_judgement(Judgements(1, 1, 1, 1, 1, 1, 1)), 3.7 / 7.);
+    }
+
+    #[test]
+    fn acc_by_judgement_without_judgements_is_zero() {
+        assert_close(acc_by_judgement(Judgements(0, 0, 0, 0, 0, 0, 0)), 0.);
+    }
+
+    #[test]
+    fn score_golden() {
+        assert_close(ScoreV2.score(1000., 100., 1000, 0, 1.), 11000.);
+        assert_close(ScoreV2.score(1200., 99., 1000, 5, 1.75), 1971.411741662);
+        assert_close(
+            NoMissPenalty.score(1200., 99., 1000, 5, 1.75),
+            1200. * 2.266246753247 * 0.825,
+        );
+    }
+
+    #[test]
+    fn ranked_score_weights_top_passes() {
+        assert_close(ranked_score(&[100., 200.]), 200. + 100. * 0.9);
+        assert_close(
+            ranked_score(&[10.; 25]),
+            (0..19).map(|i| 10. * 0.9f64.powi(i)).sum(),
+        );
+    }
+
+    #[test]
+    fn solvers_handle_unreachable_and_trivial_targets() {
+        assert_eq!(
+            min_x_acc_for_score(&ScoreV2, 1000., 1e9, 1000, 0, 1.),
+            TargetSolution::Unreachable(11000.)
+        );
+        assert_eq!(
+            min_x_acc_for_score(&ScoreV2, 1000., 1., 1000, 0, 1.),
+            TargetSolution::Always
+        );
+        assert_eq!(
+            max_misses_for_score(&ScoreV2, 1000., 1., 1000, 99., 1.),
+            TargetSolution::Always
+        );
+        assert!(matches!(
+            max_misses_for_score(&ScoreV2, 1000., 1e9, 1000, 99., 1.),
+            TargetSolution::Unreachable(_)
+        ));
+    }
+
+    #[test]
+    fn min_score_for_gain_inserts_into_top_passes() {
+        // A new top pass pushes 100 down a slot: 110 + 90 - 100 = 100.
+        assert_close(min_score_for_gain(&[100.], None, 100.), 110.);
+        // Beating the pass on the same level only adds the difference.
+        assert_close(min_score_for_gain(&[], Some(50.), 10.), 60.);
+        assert_close(min_score_for_gain(&[100.], Some(50.), 0.), 50.);
+        assert_close(min_score_for_gain(&[], None, -5.), 0.);
+    }
+
+    proptest! {
+        #[test]
+        fn min_score_for_gain_reaches_the_gain(
+            others in proptest::collection::vec(0f64..5_000., 0..30),
+            existing in proptest::option::of(0f64..5_000.),
+            gain in 0f64..2_000.,
+        ) {
+            let score = min_score_for_gain(&others, existing, gain);
+            let mut before = others.clone();
+            before.extend(existing);
+            let mut after = others.clone();
+            after.push(score.max(existing.unwrap_or(0.)));
+            prop_assert!(ranked_score(&after) - ranked_score(&before) >= gain - 1e-6);
+        }
+
+        #[test]
+        fn multipliers_are_always_finite(value in proptest::num::f64::ANY) {
+            prop_assert!(x_acc_multiplier(value).is_finite());
+            prop_assert!(speed_multiplier(value).is_finite());
+        }
+
+        #[test]
+        fn x_acc_multiplier_is_monotonic(a in 0f64..=100., b in 0f64..=100.) {
+            let (low, high) = if a <= b { (a, b) } else { (b, a) };
+            prop_assert!(x_acc_multiplier(low) <= x_acc_multiplier(high));
+        }
+
+        #[test]
+        fn miss_penalty_is_bounded_and_monotonic(misses in 0u32..10_000, tile_count in 0u32..100_000) {
+            let penalty = miss_penalty(misses, tile_count);
+            prop_assert!((0.5..=1.0).contains(&penalty));
+            prop_assert!(miss_penalty(misses + 1, tile_count) <= penalty);
+        }
+
+        #[test]
+        fn acc_by_judgement_is_a_fraction(counts in proptest::array::uniform7(any::<u64>())) {
+            let [a, b, c, d, e, f, g] = counts;
+            let acc = acc_by_judgement(Judgements(a, b, c, d, e, f, g));
+            prop_assert!(acc.is_finite());
+            prop_assert!((0.0..=1.0).contains(&acc));
+        }
+
+        #[test]
+        fn score_is_finite_and_non_negative(
+            base_score in prop_oneof![
+                -1e6f64..1e6,
+                Just(f64::NAN),
+                Just(f64::INFINITY),
+                Just(f64::NEG_INFINITY),
+            ],
+            x_acc in proptest::num::f64::ANY,
+            tile_count in any::<u32>(),
+            misses in any::<u32>(),
+            speed in proptest::num::f64::ANY,
+        ) {
+            for score in [
+                ScoreV2.score(base_score, x_acc, tile_count, misses, speed),
+                NoMissPenalty.score(base_score, x_acc, tile_count, misses, speed),
+            ] {
+                prop_assert!(score.is_finite());
+                prop_assert!(score >= 0.);
+            }
+        }
+
+        #[test]
+        fn score_grows_with_accuracy_and_drops_with_misses(
+            x_acc in 0f64..100.,
+            misses in 0u32..200,
+            tile_count in 0u32..10_000,
+            speed in 1f64..3.,
+        ) {
+            let score = ScoreV2.score(1000., x_acc, tile_count, misses, speed);
+            prop_assert!(ScoreV2.score(1000., (x_acc + 0.5).min(100.), tile_count, misses, speed) >= score);
+            prop_assert!(ScoreV2.score(1000., x_acc, tile_count, misses + 1, speed) <= score);
+        }
+
+        #[test]
+        fn min_x_acc_reaches_the_target(target in 0f64..20_000., misses in 0u32..50, speed in 1f64..3.) {
+            if let TargetSolution::Reached(x_acc) = min_x_acc_for_score(&ScoreV2, 1000., target, 1000, misses, speed) {
+                prop_assert!(ScoreV2.score(1000., x_acc, 1000, misses, speed) >= target);
+            }
+        }
+
+        #[test]
+        fn max_misses_reaches_the_target(target in 0f64..20_000., x_acc in 90f64..=100., speed in 1f64..3.) {
+            if let TargetSolution::Reached(misses) = max_misses_for_score(&ScoreV2, 1000., target, 1000, x_acc, speed) {
+                prop_assert!(ScoreV2.score(1000., x_acc, 1000, misses, speed) >= target);
+                prop_assert!(ScoreV2.score(1000., x_acc, 1000, misses + 1, speed) < target);
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use super::{ScoreBreakdown, allowed_misses, miss_penalty, speed_multiplier, x_acc_multiplier};
+
+/// One version of the TUF score formula.
+///
+/// The steps mirror [`ScoreBreakdown`], so a new formula only has to override the steps it changes.
+pub trait ScoringModel: Send + Sync {
+    /// Short id used in command options, e.g. `v2`.
+    fn id(&self) -> &'static str;
+
+    /// Name shown to users.
+    fn name(&self) -> &'static str;
+
+    fn x_acc_multiplier(&self, x_acc: f64) -> f64 {
+        x_acc_multiplier(x_acc)
+    }
+
+    fn speed_multiplier(&self, speed: f64) -> f64 {
+        speed_multiplier(speed)
+    }
+
+    fn no_miss_bonus(&self, misses: u32) -> f64 {
+        if misses == 0 { 1.1 } else { 1.0 }
+    }
+
+    fn allowed_misses(&self, tile_count: u32) -> u32 {
+        allowed_misses(tile_count)
+    }
+
+    fn miss_penalty(&self, misses: u32, tile_count: u32) -> f64 {
+        miss_penalty(misses, tile_count)
+    }
+
+    fn breakdown(
+        &self,
+        base_score: f64,
+        x_acc: f64,
+        tile_count: u32,
+        misses: u32,
+        speed: f64,
+    ) -> ScoreBreakdown {
+        // Base scores come from the API, don't let a broken one turn into NaN or a negative score.
+        let base_score = if base_score.is_finite() {
+            base_score.max(0.)
+        } else {
+            0.
+        };
+        let x_acc_multiplier = self.x_acc_multiplier(x_acc);
+        let speed_multiplier = self.speed_multiplier(speed);
+        let no_miss_bonus = self.no_miss_bonus(misses);
+        let miss_penalty = self.miss_penalty(misses, tile_count);
+
+        ScoreBreakdown {
+            model: self.name(),
+            base_score,
+            x_acc,
+            speed,
+            misses,
+            tile_count,
+            x_acc_multiplier,
+            speed_multiplier,
+            no_miss_bonus,
+            allowed_misses: self.allowed_misses(tile_count),
+            miss_penalty,
+            final_score: base_score
+                * x_acc_multiplier
+                * speed_multiplier
+                * no_miss_bonus
+                * miss_penalty,
+        }
+    }
+
+    fn score(&self, base_score: f64, x_acc: f64, tile_count: u32, misses: u32, speed: f64) -> f64 {
+        self.breakdown(base_score, x_acc, tile_count, misses, speed)
+            .final_score
+    }
+}
+
+/// The formula TUF uses today.
+pub struct ScoreV2;
+
+impl ScoringModel for ScoreV2 {
+    fn id(&self) -> &'static str {
+        "v2"
+    }
+
+    fn name(&self) -> &'static str {
+        "Score v2 (current)"
+    }
+}
+
+/// Score v2 with misses costing only the no miss bonus, to see what the miss penalty takes away.
+pub struct NoMissPenalty;
+
+impl ScoringModel for NoMissPenalty {
+    fn id(&self) -> &'static str {
+        "v2nmp"
+    }
+
+    fn name(&self) -> &'static str {
+        "Score v2 without miss penalty"
+    }
+
+    fn allowed_misses(&self, _tile_count: u32) -> u32 {
+        0
+    }
+
+    fn miss_penalty(&self, _misses: u32, _tile_count: u32) -> f64 {
+        1.0
+    }
+}
+
+/// Every known model, the default one first. Proposed formulas get added here.
+pub static MODELS: &[&dyn ScoringModel] = &[&ScoreV2, &NoMissPenalty];
+
+pub fn default_model() -> &'static dyn ScoringModel {
+    MODELS[0]
+}
+
+pub fn model_by_id(id: &str) -> Option<&'static dyn ScoringModel> {
+    MODELS.iter().copied().find(|model| model.id() == id)
+}
@@ -0,0 +1,404 @@
+use std::fmt;
+
+use crate::tuforums::clear_info::Judgements;
+
+/// Parses judgements typed or pasted by users.
+///
+/// Accepts plain counts (`0 3 12 1200 10 2 0`, commas or slashes work too), labelled counts
+/// (`perfect=1200 early=3`) and the results text from the game (`Too Early 0 Early! 3 ...`).
+/// Labels that aren't given default to 0. Plain counts may also be partial:
+/// 1 value is perfects, 3 are `ep p lp` and 5 are `early ep p lp late`.
+pub fn parse_judgements(input: &str) -> Result<Judgements, JudgementParseError> {
+    let mut counts = [0u64; 7];
+    let mut seen = [false; 7];
+    let mut positional = Vec::new();
+    let mut labelled = false;
+    let mut words: Vec<Token> = Vec::new();
+
+    for token in tokenize(input) {
+        if !token.is_numeric() {
+            words.push(token);
+            continue;
+        }
+
+        if words.is_empty() {
+            if labelled {
+                return Err(JudgementParseError::MixedInput {
+                    position: token.start,
+                });
+            }
+            positional.push((parse_count(&token)?, token.start));
+            continue;
+        }
+
+        if let Some((_, position)) = positional.first() {
+            return Err(JudgementParseError::MixedInput {
+                position: *position,
+            });
+        }
+        labelled = true;
+
+        let label = resolve_label(&words)?;
+        words.clear();
+
+        let Label::Judgement(index) = label else {
+            continue;
+        };
+
+        if seen[index] {
+            return Err(JudgementParseError::DuplicateLabel {
+                label: LABEL_NAMES[index],
+                position: token.start,
+            });
+        }
+        seen[index] = true;
+        counts[index] = parse_count(&token)?;
+    }
+
+    if let Some(word) = words.first() {
+        // Trailing words only matter if they were meant to be a label.
+        match resolve_label(&words) {
+            Ok(Label::Ignored) => {}
+            Ok(Label::Judgement(_)) => {
+                return Err(JudgementParseError::MissingValue {
+                    label: word.text.to_string(),
+                    position: word.start,
+                });
+            }
+            Err(error) if labelled || !positional.is_empty() => return Err(error),
+            Err(_) => {}
+        }
+    }
+
+    if labelled {
+        let [a, b, c, d, e, f, g] = counts;
+        return Ok(Judgements(a, b, c, d, e, f, g));
+    }
+
+    let values = positional
+        .iter()
+        .map(|(value, _)| *value)
+        .collect::<Vec<_>>();
+    match values.as_slice() {
+        [] => Err(JudgementParseError::Empty),
+        [p] => Ok(Judgements(0, 0, 0, *p, 0, 0, 0)),
+        [ep, p, lp] => Ok(Judgements(0, 0, *ep, *p, *lp, 0, 0)),
+        [e, ep, p, lp, l] => Ok(Judgements(0, *e, *ep, *p, *lp, *l, 0)),
+        [a, b, c, d, e, f, g] => Ok(Judgements(*a, *b, *c, *d, *e, *f, *g)),
+        _ => Err(JudgementParseError::UnsupportedCount {
+            count: values.len(),
+            position: positional.last().map_or(0, |(_, position)| *position),
+        }),
+    }
+}
+
+fn parse_count(token: &Token) -> Result<u64, JudgementParseError> {
+    token
+        .text
+        .parse::<u64>()
+        .map_err(|_| JudgementParseError::InvalidNumber {
+            token: token.text.to_string(),
+            position: token.start,
+        })
+}
+
+const LABEL_NAMES: [&str; 7] = [
+    "too early",
+    "early",
+    "early perfect",
+    "perfect",
+    "late perfect",
+    "late",
+    "too late",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JudgementParseError {
+    Empty,
+    InvalidNumber {
+        token: String,
+        position: usize,
+    },
+    UnknownLabel {
+        token: String,
+        position: usize,
+    },
+    MissingValue {
+        label: String,
+        position: usize,
+    },
+    DuplicateLabel {
+        label: &'static str,
+        position: usize,
+    },
+    MixedInput {
+        position: usize,
+    },
+    UnsupportedCount {
+        count: usize,
+        position: usize,
+    },
+}
+
+impl JudgementParseError {
+    fn position(&self) -> Option<usize> {
+        match self {
+            JudgementParseError::Empty => None,
+            JudgementParseError::InvalidNumber { position, .. }
+            | JudgementParseError::UnknownLabel { position, .. }
+            | JudgementParseError::MissingValue { position, .. }
+            | JudgementParseError::DuplicateLabel { position, .. }
+            | JudgementParseError::MixedInput { position }
+            | JudgementParseError::UnsupportedCount { position, .. } => Some(*position),
+        }
+    }
+
+    /// The error message with the offending line of `input` and a marker under the bad token.
+    pub fn render(&self, input: &str) -> String {
+        let Some(position) = self.position() else {
+            return format!("⚠️ {}", self);
+        };
+
+        let line_start = input[..position].rfind('\n').map_or(0, |index| index + 1);
+        let line_end = input[position..]
+            .find('\n')
+            .map_or(input.len(), |index| position + index);
+        let line = &input[line_start..line_end];
+        let column = input[line_start..position].chars().count();
+        let width = input[position..line_end]
+            .split(is_separator)
+            .next()
+            .map_or(1, |token| token.chars().count().max(1));
+
+        format!(
+            "⚠️ {}\n```\n{}\n{}{}\n```",
+            self,
+            line,
+            " ".repeat(column),
+            "^".repeat(width)
+        )
+    }
+}
+
+impl fmt::Display for JudgementParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JudgementParseError::Empty => write!(f, "No judgements given"),
+            JudgementParseError::InvalidNumber { token, .. } => {
+                write!(f, "`{}` is not a valid judgement count", token)
+            }
+            JudgementParseError::UnknownLabel { token, .. } => write!(
+                f,
+                "Unknown judgement `{}`, use too_early, early, ep, perfect, lp, late or too_late",
+                token
+            ),
+            JudgementParseError::MissingValue { label, .. } => {
+                write!(f, "`{}` has no count after it", label)
+            }
+            JudgementParseError::DuplicateLabel { label, .. } => {
+                write!(f, "{} is given more than once", label)
+            }
+            JudgementParseError::MixedInput { .. } => write!(
+                f,
+                "Don't mix labelled and plain counts, label every count or none of them"
+            ),
+            JudgementParseError::UnsupportedCount { count, .. } => write!(
+                f,
+                "Got {} counts, give 7 (too early to too late), 5, 3 or just the perfects",
+                count
+            ),
+        }
+    }
+}
+
+enum Label {
+    Judgement(usize),
+    /// Numbers from the results screen that aren't judgements, like the accuracy.
+    Ignored,
+}
+
+/// Finds the label the words end with, so headers like "Results" in pasted text are skipped.
+fn resolve_label(words: &[Token]) -> Result<Label, JudgementParseError> {
+    for length in (1..=words.len().min(3)).rev() {
+        let phrase = words[words.len() - length..]
+            .iter()
+            .map(|word| normalize(word.text))
+            .collect::<String>();
+
+        if let Some(label) = label_for(&phrase) {
+            return Ok(label);
+        }
+    }
+
+    let last = words.last().expect("labels have at least one word");
+    Err(JudgementParseError::UnknownLabel {
+        token: last.text.to_string(),
+        position: last.start,
+    })
+}
+
+fn label_for(phrase: &str) -> Option<Label> {
+    let index = match phrase {
+        "tooearly" | "earlydouble" | "te" | "early!!" => 0,
+        "early" | "earlysingle" | "e" | "early!" => 1,
+        "ep" | "eperfect" | "earlyperfect" | "eperfect!" => 2,
+        "p" | "perfect" | "perfect!" => 3,
+        "lp" | "lperfect" | "lateperfect" | "lperfect!" => 4,
+        "late" | "latesingle" | "l" | "late!" => 5,
+        "toolate" | "latedouble" | "tl" | "late!!" => 6,
+        "acc" | "accuracy" | "xacc" | "xaccuracy" | "checkpoint" | "checkpoints" => {
+            return Some(Label::Ignored);
+        }
+        _ => return None,
+    };
+
+    Some(Label::Judgement(index))
+}
+
+fn normalize(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric() || *c == '!')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+struct Token<'a> {
+    text: &'a str,
+    start: usize,
+}
+
+impl Token<'_> {
+    fn is_numeric(&self) -> bool {
+        self.text
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_digit() || c == '-' || c == '+')
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || matches!(c, ',' | '/' | ';' | '|' | '=' | ':')
+}
+
+fn tokenize(input: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+
+    for (index, c) in input.char_indices() {
+        match (is_separator(c), start) {
+            (true, Some(token_start)) => {
+                tokens.push(Token {
+                    text: &input[token_start..index],
+                    start: token_start,
+                });
+                start = None;
+            }
+            (false, None) => start = Some(index),
+            _ => {}
+        }
+    }
+
+    if let Some(token_start) = start {
+        tokens.push(Token {
+            text: &input[token_start..],
+            start: token_start,
+        });
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JudgementParseError, parse_judgements};
+    use crate::tuforums::clear_info::Judgements;
+
+    #[test]
+    fn plain_counts_with_any_separator() {
+        let expected = Judgements(0, 3, 12, 1200, 10, 2, 1);
+        assert_eq!(parse_judgements("0 3 12 1200 10 2 1"), Ok(expected));
+        assert_eq!(parse_judgements("0,3,12,1200,10,2,1"), Ok(expected));
+        assert_eq!(parse_judgements("0/3/12/1200/10/2/1"), Ok(expected));
+        assert_eq!(parse_judgements(" 0, 3 / 12  1200,10 2 1 "), Ok(expected));
+    }
+
+    #[test]
+    fn too_early_is_a_miss_not_a_tile() {
+        let judgements = parse_judgements("4 3 12 1200 10 2 1").unwrap();
+        assert_eq!(judgements.tile_count(), 1228);
+        assert_eq!(judgements.misses(), 4);
+    }
+
+    #[test]
+    fn partial_plain_counts() {
+        assert_eq!(
+            parse_judgements("1200"),
+            Ok(Judgements(0, 0, 0, 1200, 0, 0, 0))
+        );
+        assert_eq!(
+            parse_judgements("12 1200 10"),
+            Ok(Judgements(0, 0, 12, 1200, 10, 0, 0))
+        );
+        assert_eq!(
+            parse_judgements("3 12 1200 10 2"),
+            Ok(Judgements(0, 3, 12, 1200, 10, 2, 0))
+        );
+    }
+
+    #[test]
+    fn labelled_counts_default_to_zero() {
+        assert_eq!(
+            parse_judgements("perfect=1200 early=3 lp: 10"),
+            Ok(Judgements(0, 3, 0, 1200, 10, 0, 0))
+        );
+        assert_eq!(
+            parse_judgements("too_early=1 too late=2"),
+            Ok(Judgements(1, 0, 0, 0, 0, 0, 2))
+        );
+    }
+
+    #[test]
+    fn pasted_results_text() {
+        let input = "Results\nToo Early 1\nEarly! 3\nEPerfect! 12\nPerfect! 1200\nLPerfect! 10\nLate! 2\nToo Late 0\nX-Accuracy 99.12%";
+        assert_eq!(
+            parse_judgements(input),
+            Ok(Judgements(1, 3, 12, 1200, 10, 2, 0))
+        );
+    }
+
+    #[test]
+    fn errors_point_at_the_bad_token() {
+        let input = "perfect=12x0 early=3";
+        let error = parse_judgements(input).unwrap_err();
+        assert_eq!(
+            error,
+            JudgementParseError::InvalidNumber {
+                token: "12x0".to_string(),
+                position: 8
+            }
+        );
+        assert!(
+            error
+                .render(input)
+                .ends_with("perfect=12x0 early=3\n        ^^^^\n```")
+        );
+
+        assert!(matches!(
+            parse_judgements("perfekt=1200"),
+            Err(JudgementParseError::UnknownLabel { position: 0, .. })
+        ));
+        assert!(matches!(
+            parse_judgements("1 2 3 4"),
+            Err(JudgementParseError::UnsupportedCount { count: 4, .. })
+        ));
+        assert!(matches!(
+            parse_judgements("perfect=1 perfect=2"),
+            Err(JudgementParseError::DuplicateLabel { position: 18, .. })
+        ));
+        assert!(matches!(
+            parse_judgements("3 perfect=1200"),
+            Err(JudgementParseError::MixedInput { position: 0 })
+        ));
+        assert_eq!(parse_judgements("  "), Err(JudgementParseError::Empty));
+    }
+}
@@ -1,6 +1,10 @@
 use std::{sync::Arc, vec};
 
-use commands::leaderboard::LeaderboardHandler;
+use commands::{
+    calculator::CalculatorHandler, leaderboard::LeaderboardHandler, level::LevelSearchHandler,
+    profile::ProfileHandler,
+    random_lvl::LevelClearsHandler,
+};
 use database::Database;
 use dotenv::dotenv;
 use serenity::{
@@ -11,16 +15,21 @@ use serenity::{
     },
     async_trait,
 };
-use tasks::{change_status, daily_random_level};
+use tasks::{
+    change_status, daily_random_level, global_feeds, pass_feed, rank_tracker, role_sync,
+    weekly_digest,
+};
 use tokio::sync::Mutex;
 
 use crate::{cache_manager::CacheManager, tasks::clear_cache};
 use chrono::Local;
 
+mod adofai;
 mod cache_manager;
 mod commands;
 mod database;
 mod formulas;
+mod judgement_parser;
 mod tasks;
 mod tuforums;
 mod utils;
@@ -40,11 +49,37 @@ impl EventHandler for Handler {
                     None
                 }
                 "leaderboard" => {
-                    commands::leaderboard::run(&ctx, &command).await.unwrap();
+                    commands::leaderboard::run(&ctx, &command, &self.database, &self.cache_manager)
+                        .await
+                        .unwrap();
+                    None
+                }
+                "compare" => {
+                    commands::compare::run(&ctx, &command, &self.database, &self.cache_manager)
+                        .await
+                        .unwrap();
+                    None
+                }
+                "level" => {
+                    commands::level::run(&ctx, &command, &self.cache_manager)
+                        .await
+                        .unwrap();
                     None
                 }
                 "calcscore" => {
-                    commands::calc_score::run(&ctx, &command).await.unwrap();
+                    commands::calc_score::run(&ctx, &command, &self.database, &self.cache_manager)
+                        .await
+                        .unwrap();
+                    None
+                }
+                "calcscore_j" => {
+                    commands::calc_score_j::run(&ctx, &command).await.unwrap();
+                    None
+                }
+                "calctarget" => {
+                    commands::calc_target::run(&ctx, &command, &self.database, &self.cache_manager)
+                        .await
+                        .unwrap();
                     None
                 }
                 "calcacc" => {
@@ -66,17 +101,31 @@ impl EventHandler for Handler {
                     None
                 }
                 "random_lvl" => {
-                    commands::random_lvl::run(&ctx, &command).await.unwrap();
+                    commands::random_lvl::run(&ctx, &command, &self.cache_manager)
+                        .await
+                        .unwrap();
                     None
                 }
                 "link" => {
-                    commands::link::run(&ctx, &command, &self.database)
+                    commands::link::run(&ctx, &command, &self.database, &self.cache_manager)
+                        .await
+                        .unwrap();
+                    None
+                }
+                "notify" => {
+                    commands::notify::run(&ctx, &command, &self.database)
                         .await
                         .unwrap();
                     None
                 }
                 "setup" => {
-                    commands::setup::run(&ctx, &command, &self.database)
+                    commands::setup::run(&ctx, &command, &self.database, &self.cache_manager)
+                        .await
+                        .unwrap();
+                    None
+                }
+                "analyze" => {
+                    commands::analyze::run(&ctx, &command, &self.cache_manager)
                         .await
                         .unwrap();
                     None
@@ -98,6 +147,14 @@ impl EventHandler for Handler {
                     println!("Cannot respond to slash command: {:?}", why);
                 }
             }
+        } else if let Interaction::Autocomplete(autocomplete) = interaction
+            && let Err(why) =
+                commands::autocomplete::run(&ctx, &autocomplete, &self.cache_manager).await
+        {
+            log_message(
+                format!("Cannot respond to autocomplete: {:?}", why).as_str(),
+                LogLevel::Warning,
+            );
         }
     }
 
@@ -107,6 +164,8 @@ impl EventHandler for Handler {
             vec![
                 commands::help::register(),
                 commands::calc_score::register(),
+                commands::calc_score_j::register(),
+                commands::calc_target::register(),
                 commands::calc_acc::register(),
                 commands::leaderboard::register(),
                 commands::ping::register(),
@@ -116,6 +175,10 @@ impl EventHandler for Handler {
                 commands::link::register(),
                 commands::setup::register(),
                 commands::cache_info::register(),
+                commands::analyze::register(),
+                commands::level::register(),
+                commands::compare::register(),
+                commands::notify::register(),
             ],
         )
         .await;
@@ -127,6 +190,11 @@ impl EventHandler for Handler {
         }
 
         daily_random_level::run_task(&ctx, &self.database).await;
+        weekly_digest::run_task(&ctx, &self.database, &self.cache_manager).await;
+        rank_tracker::run_task(&ctx, &self.database, &self.cache_manager).await;
+        pass_feed::run_task(&ctx, &self.database).await;
+        global_feeds::run_task(&ctx, &self.database).await;
+        role_sync::run_task(&ctx, &self.database, &self.cache_manager).await;
         change_status::run_task(&ctx).await;
         // actix_web_main::run_task(&self.cache_manager)
         //     .await
@@ -156,10 +224,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut client = Client::builder(token_env, GatewayIntents::all())
         .event_handler(Handler {
+            database: database.clone(),
+            cache_manager: cache_manager.clone(),
+        })
+        .event_handler(LeaderboardHandler {
+            database: database.clone(),
+            cache_manager: cache_manager.clone(),
+        })
+        .event_handler(CalculatorHandler {
             database,
-            cache_manager,
+            cache_manager: cache_manager.clone(),
+        })
+        .event_handler(LevelSearchHandler {
+            cache_manager: cache_manager.clone(),
+        })
+        .event_handler(LevelClearsHandler {
+            cache_manager: cache_manager.clone(),
         })
-        .event_handler(LeaderboardHandler)
+        .event_handler(ProfileHandler { cache_manager })
         .activity(ActivityData::watching("TUForums"))
         .await?;
 
@@ -5,8 +5,9 @@ use tokio::{spawn, sync::Mutex};
 
 use crate::cache_manager::CacheManager;
 
+#[allow(dead_code)]
 pub async fn run_task(
-    cache_manager: &Arc<Mutex<CacheManager>>,
+    _cache_manager: &Arc<Mutex<CacheManager>>,
 ) -> std::result::Result<(), std::io::Error> {
     spawn(async move {
         HttpServer::new(|| {
@@ -8,7 +8,7 @@ use tokio::time::sleep;
 
 use crate::{
     LogLevel,
-    commands::random_lvl::level_embed,
+    commands::random_lvl::level_view,
     database::Database,
     log_message,
     tuforums::level::{get_level, request_random_lvl_id},
@@ -63,19 +63,21 @@ pub async fn run_task(ctx: &Context, database: &Database) {
                 }
             };
 
-            let level_embed = level_embed(level);
-            let message = CreateMessage::new().embed(level_embed);
+            let (level_embed, components) = level_view(level, None).await;
+            let message = CreateMessage::new()
+                .embed(level_embed)
+                .components(components);
 
             for doc in results {
                 let channel_id = doc.get_str("channel_id").unwrap();
                 let channel_id = ChannelId::new(channel_id.parse::<u64>().unwrap());
 
-                if let Ok(channel) = channel_id.to_channel(&ctx).await {
-                    if let Channel::Guild(g_channel) = channel {
-                        sleep(Duration::from_secs(1)).await; // Sleep for 1 second to avoid rate limits
-                        if let Err(e) = g_channel.send_message(&ctx.http, message.clone()).await {
-                            eprintln!("Failed to send embed: {:?}", e);
-                        }
+                if let Ok(channel) = channel_id.to_channel(&ctx).await
+                    && let Channel::Guild(g_channel) = channel
+                {
+                    sleep(Duration::from_secs(1)).await; // Sleep for 1 second to avoid rate limits
+                    if let Err(e) = g_channel.send_message(&ctx.http, message.clone()).await {
+                        eprintln!("Failed to send embed: {:?}", e);
                     }
                 }
             }
@@ -0,0 +1,428 @@
+use std::{collections::HashMap, time::Duration};
+
+use mongodb::{
+    Collection,
+    bson::{Document, doc},
+};
+use serenity::{
+    all::{ChannelId, Context, CreateEmbed, CreateMessage},
+    futures::TryStreamExt,
+};
+use tokio::time::sleep;
+
+use crate::{
+    LogLevel,
+    commands::clear::clear_embed,
+    database::Database,
+    log_message,
+    tuforums::{
+        clear_info::{get_clear_info, get_recent_passes},
+        difficulty::{DifficultyRange, DifficultyTier},
+        level::{LevelFilter, LevelSearchResult, search_levels},
+    },
+};
+
+/// How often TUF is polled for new events.
+const INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Recent passes checked for world's firsts on each poll.
+const PASS_WINDOW: u32 = 50;
+
+/// Newest levels checked on each poll, for new and newly rated levels.
+const RECENT_LEVELS: u32 = 50;
+
+/// Levels re-checked for rerates on each poll. The window walks through the whole
+/// catalog, so a rerate of an old level shows up once the window gets to it.
+const SWEEP_LEVELS: u32 = 100;
+
+/// Most events of one kind posted per poll, so a long downtime doesn't flood channels.
+const MAX_EVENTS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FeedKind {
+    WorldsFirsts,
+    NewLevels,
+    Rerates,
+}
+
+impl FeedKind {
+    const ALL: [FeedKind; 3] = [
+        FeedKind::WorldsFirsts,
+        FeedKind::NewLevels,
+        FeedKind::Rerates,
+    ];
+
+    /// The `setups` type subscribing a channel to this feed.
+    fn setup_type(&self) -> &'static str {
+        match self {
+            FeedKind::WorldsFirsts => "wf-feed-channel",
+            FeedKind::NewLevels => "new-level-feed-channel",
+            FeedKind::Rerates => "rerate-feed-channel",
+        }
+    }
+}
+
+struct Subscription {
+    kind: FeedKind,
+    channel_id: ChannelId,
+    /// Every difficulty when `None`.
+    range: Option<DifficultyRange>,
+}
+
+impl Subscription {
+    /// Whether one of the event's difficulties falls in the range.
+    fn wants(&self, difficulties: &[&str]) -> bool {
+        let Some(range) = &self.range else {
+            return true;
+        };
+
+        difficulties
+            .iter()
+            .filter_map(|difficulty| difficulty.parse::<DifficultyTier>().ok())
+            .any(|tier| range.contains(&tier))
+    }
+}
+
+/// Whether the difficulty is on the PGU scale, unrated levels have other names.
+fn is_rated(difficulty: &str) -> bool {
+    difficulty
+        .parse::<DifficultyTier>()
+        .is_ok_and(|tier| tier.order().is_some())
+}
+
+struct FeedEvent {
+    kind: FeedKind,
+    /// Difficulties the subscription ranges are checked against.
+    difficulties: Vec<String>,
+    message: CreateMessage,
+}
+
+pub async fn run_task(ctx: &Context, database: &Database) {
+    log_message("Launching global feeds task", LogLevel::Info);
+
+    let (Some(setups), Some(states), Some(levels)) = (
+        database.get_collection("onuzglorp-bot", "setups").await,
+        database.get_collection("onuzglorp-bot", "feed_state").await,
+        database
+            .get_collection("onuzglorp-bot", "level_difficulties")
+            .await,
+    ) else {
+        log_message(
+            "Database is not connected, global feeds not started",
+            LogLevel::Warning,
+        );
+        return;
+    };
+
+    let ctx = ctx.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let mut events = Vec::new();
+
+            match worlds_first_events(&states).await {
+                Ok(found) => events.extend(found),
+                Err(e) => log_message(
+                    format!("World's first feed failed: {}", e).as_str(),
+                    LogLevel::Error,
+                ),
+            }
+            match level_events(&states, &levels).await {
+                Ok(found) => events.extend(found),
+                Err(e) => log_message(
+                    format!("Level feeds failed: {}", e).as_str(),
+                    LogLevel::Error,
+                ),
+            }
+
+            if !events.is_empty() {
+                match subscriptions(&setups).await {
+                    Ok(subscriptions) => fan_out(&ctx, &subscriptions, events).await,
+                    Err(e) => log_message(
+                        format!("Couldn't load feed subscriptions: {}", e).as_str(),
+                        LogLevel::Error,
+                    ),
+                }
+            }
+
+            sleep(INTERVAL).await;
+        }
+    });
+}
+
+async fn subscriptions(
+    setups: &Collection<Document>,
+) -> Result<Vec<Subscription>, Box<dyn std::error::Error + Send + Sync>> {
+    let types = FeedKind::ALL
+        .iter()
+        .map(|kind| kind.setup_type())
+        .collect::<Vec<_>>();
+
+    let subscriptions = setups
+        .find(doc! { "type": { "$in": types } })
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?
+        .iter()
+        .filter_map(|doc| {
+            let setup_type = doc.get_str("type").ok()?;
+            let range = match (doc.get_str("min_diff"), doc.get_str("max_diff")) {
+                (Ok(min), Ok(max)) => {
+                    Some(DifficultyRange::new(min.parse().ok()?, max.parse().ok()?).ok()?)
+                }
+                _ => None,
+            };
+
+            Some(Subscription {
+                kind: *FeedKind::ALL
+                    .iter()
+                    .find(|kind| kind.setup_type() == setup_type)?,
+                channel_id: ChannelId::new(doc.get_str("channel_id").ok()?.parse().ok()?),
+                range,
+            })
+        })
+        .collect();
+
+    Ok(subscriptions)
+}
+
+async fn fan_out(ctx: &Context, subscriptions: &[Subscription], events: Vec<FeedEvent>) {
+    for event in events {
+        let difficulties = event
+            .difficulties
+            .iter()
+            .map(|difficulty| difficulty.as_str())
+            .collect::<Vec<_>>();
+
+        for subscription in subscriptions
+            .iter()
+            .filter(|subscription| subscription.kind == event.kind)
+            .filter(|subscription| subscription.wants(&difficulties))
+        {
+            sleep(Duration::from_secs(1)).await; // Sleep for 1 second to avoid rate limits
+            if let Err(e) = subscription
+                .channel_id
+                .send_message(&ctx.http, event.message.clone())
+                .await
+            {
+                log_message(
+                    format!("Failed to send feed event: {:?}", e).as_str(),
+                    LogLevel::Error,
+                );
+            }
+        }
+    }
+}
+
+async fn get_state(
+    states: &Collection<Document>,
+    id: &str,
+) -> Result<Option<Document>, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(states.find_one(doc! { "_id": id }).await?)
+}
+
+async fn set_state(
+    states: &Collection<Document>,
+    id: &str,
+    state: Document,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    states
+        .update_one(doc! { "_id": id }, doc! { "$set": state })
+        .upsert(true)
+        .await?;
+    Ok(())
+}
+
+/// New world's firsts since the last poll. The first poll only remembers where it is.
+async fn worlds_first_events(
+    states: &Collection<Document>,
+) -> Result<Vec<FeedEvent>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut passes = get_recent_passes(PASS_WINDOW).await?;
+    passes.sort_by_key(|pass| pass.id);
+
+    let Some(latest) = passes.last().map(|pass| pass.id) else {
+        return Ok(Vec::new());
+    };
+
+    let last_seen = get_state(states, "worlds_firsts")
+        .await?
+        .and_then(|state| state.get_i64("last_pass_id").ok());
+    set_state(
+        states,
+        "worlds_firsts",
+        doc! { "last_pass_id": latest as i64 },
+    )
+    .await?;
+
+    let Some(last_seen) = last_seen else {
+        return Ok(Vec::new());
+    };
+
+    let new_firsts = passes
+        .into_iter()
+        .filter(|pass| pass.is_worlds_first && pass.id as i64 > last_seen)
+        .collect::<Vec<_>>();
+
+    let mut events = Vec::new();
+    for pass in new_firsts.into_iter().rev().take(MAX_EVENTS).rev() {
+        let clear = match get_clear_info(&pass.id).await {
+            Ok(clear) => clear,
+            Err(e) => {
+                log_message(
+                    format!("Couldn't fetch pass {}: {}", pass.id, e).as_str(),
+                    LogLevel::Warning,
+                );
+                continue;
+            }
+        };
+
+        events.push(FeedEvent {
+            kind: FeedKind::WorldsFirsts,
+            message: CreateMessage::new()
+                .content(format!(
+                    "🏆 **World's First** on **{} - {}** ({}) by **{}**",
+                    pass.level_artist, pass.level_title, pass.difficulty, pass.player_name
+                ))
+                .embed(clear_embed(pass.id, clear)),
+            difficulties: vec![pass.difficulty],
+        });
+    }
+
+    Ok(events)
+}
+
+fn level_message(title: &str, level: &LevelSearchResult, detail: String) -> CreateMessage {
+    CreateMessage::new().embed(
+        CreateEmbed::new()
+            .title(title)
+            .description(format!(
+                "**{} - {}**\n{}\n[Open on TUF](https://tuforums.com/levels/{}) | `/level id:{}`",
+                level.artist, level.title, detail, level.id, level.id
+            ))
+            .color(0xFF69B4),
+    )
+}
+
+/// New, newly rated and rerated levels, found by comparing the newest levels and a
+/// window walking through the catalog with the difficulties stored in `level_difficulties`.
+async fn level_events(
+    states: &Collection<Document>,
+    stored: &Collection<Document>,
+) -> Result<Vec<FeedEvent>, Box<dyn std::error::Error + Send + Sync>> {
+    let state = get_state(states, "levels").await?;
+    let last_level_id = state
+        .as_ref()
+        .and_then(|state| state.get_i64("last_level_id").ok());
+    let sweep_offset = state
+        .as_ref()
+        .and_then(|state| state.get_i64("sweep_offset").ok())
+        .unwrap_or(0) as u32;
+
+    let filter = LevelFilter {
+        sort: Some("RECENT_DESC"),
+        ..Default::default()
+    };
+    let recent = search_levels(&filter, 0, RECENT_LEVELS).await?;
+    let sweep = search_levels(&filter, sweep_offset, SWEEP_LEVELS).await?;
+
+    let mut levels = HashMap::new();
+    for level in recent.levels.into_iter().chain(sweep.levels) {
+        levels.insert(level.id, level);
+    }
+
+    let ids = levels.keys().map(|id| *id as i64).collect::<Vec<_>>();
+    let known = stored
+        .find(doc! { "_id": { "$in": &ids } })
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?
+        .iter()
+        .filter_map(|doc| {
+            Some((
+                doc.get_i64("_id").ok()? as u32,
+                doc.get_str("difficulty").ok()?.to_string(),
+            ))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut levels = levels.into_values().collect::<Vec<_>>();
+    levels.sort_by_key(|level| level.id);
+
+    let (mut new_levels, mut rerates) = (Vec::new(), Vec::new());
+    for level in &levels {
+        match known.get(&level.id) {
+            // A first poll only fills `level_difficulties`.
+            None if last_level_id.is_some_and(|last| level.id as i64 > last) => {
+                new_levels.push(FeedEvent {
+                    kind: FeedKind::NewLevels,
+                    message: level_message(
+                        "🆕 New level",
+                        level,
+                        format!("Difficulty: **{}**", level.difficulty),
+                    ),
+                    difficulties: vec![level.difficulty.clone()],
+                });
+            }
+            Some(previous) if *previous != level.difficulty => {
+                // Unrated levels getting a difficulty on the scale are new to the ranked pool.
+                if !is_rated(previous) && is_rated(&level.difficulty) {
+                    new_levels.push(FeedEvent {
+                        kind: FeedKind::NewLevels,
+                        message: level_message(
+                            "✅ Level rated",
+                            level,
+                            format!("Rated **{}** (was {})", level.difficulty, previous),
+                        ),
+                        difficulties: vec![level.difficulty.clone()],
+                    });
+                } else {
+                    rerates.push(FeedEvent {
+                        kind: FeedKind::Rerates,
+                        message: level_message(
+                            "🔁 Rerate",
+                            level,
+                            format!("**{}** ➜ **{}**", previous, level.difficulty),
+                        ),
+                        difficulties: vec![previous.clone(), level.difficulty.clone()],
+                    });
+                }
+            }
+            _ => {}
+        }
+
+        if known.get(&level.id) != Some(&level.difficulty) {
+            stored
+                .update_one(
+                    doc! { "_id": level.id as i64 },
+                    doc! { "$set": { "difficulty": &level.difficulty } },
+                )
+                .upsert(true)
+                .await?;
+        }
+    }
+
+    let next_offset = sweep_offset + SWEEP_LEVELS;
+    set_state(
+        states,
+        "levels",
+        doc! {
+            "last_level_id": levels
+                .iter()
+                .map(|level| level.id as i64)
+                .chain(last_level_id)
+                .max()
+                .unwrap_or(0),
+            "sweep_offset": if next_offset as u64 >= sweep.total { 0 } else { next_offset as i64 },
+        },
+    )
+    .await?;
+
+    let skip_new = new_levels.len().saturating_sub(MAX_EVENTS);
+    let skip_rerates = rerates.len().saturating_sub(MAX_EVENTS);
+
+    Ok(new_levels
+        .into_iter()
+        .skip(skip_new)
+        .chain(rerates.into_iter().skip(skip_rerates))
+        .collect())
+}
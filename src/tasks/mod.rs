@@ -2,3 +2,8 @@ pub mod actix_web_main;
 pub mod change_status;
 pub mod clear_cache;
 pub mod daily_random_level;
+pub mod global_feeds;
+pub mod pass_feed;
+pub mod rank_tracker;
+pub mod role_sync;
+pub mod weekly_digest;
@@ -0,0 +1,253 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use mongodb::{
+    Collection,
+    bson::{Document, doc},
+};
+use serenity::{
+    all::{ChannelId, Context, CreateMessage, GuildId, UserId},
+    futures::TryStreamExt,
+};
+use tokio::time::sleep;
+
+use crate::{
+    LogLevel,
+    commands::{clear::clear_embed, server_leaderboard::linked_members},
+    database::Database,
+    log_message,
+    tuforums::{
+        clear_info::{ClearInfo, get_clear_info},
+        profile::get_passes,
+    },
+};
+
+/// How often linked players' passes are checked.
+const INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Most passes posted for one player per check, older ones are skipped.
+const MAX_PASSES_PER_CHECK: usize = 5;
+
+pub async fn run_task(ctx: &Context, database: &Database) {
+    log_message("Launching pass feed task", LogLevel::Info);
+
+    let (Some(setups), Some(states), Some(users)) = (
+        database.get_collection("onuzglorp-bot", "setups").await,
+        database
+            .get_collection("onuzglorp-bot", "pass_feed_state")
+            .await,
+        database.get_collection("onuzglorp-bot", "users").await,
+    ) else {
+        log_message(
+            "Database is not connected, pass feed not started",
+            LogLevel::Warning,
+        );
+        return;
+    };
+
+    let ctx = ctx.clone();
+    let database = database.clone();
+
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = check_passes(&ctx, &database, &setups, &states, &users).await {
+                log_message(format!("Pass feed failed: {}", e).as_str(), LogLevel::Error);
+            }
+
+            sleep(INTERVAL).await;
+        }
+    });
+}
+
+async fn check_passes(
+    ctx: &Context,
+    database: &Database,
+    setups: &Collection<Document>,
+    states: &Collection<Document>,
+    users: &Collection<Document>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let channels = setups
+        .find(doc! { "type": "pass-feed-channel" })
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?
+        .iter()
+        .filter_map(|doc| {
+            Some((
+                GuildId::new(doc.get_str("guild_id").ok()?.parse().ok()?),
+                ChannelId::new(doc.get_str("channel_id").ok()?.parse().ok()?),
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    if channels.is_empty() {
+        return Ok(());
+    }
+
+    let opted_out = users
+        .find(doc! { "notify_passes": false })
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?
+        .iter()
+        .filter_map(|doc| Some(UserId::new(doc.get_i64("_id").ok()? as u64)))
+        .collect::<HashSet<_>>();
+
+    // The channels each profile's passes go to.
+    let mut audiences = HashMap::<u64, Vec<ChannelId>>::new();
+    for (guild_id, channel_id) in channels {
+        match linked_members(ctx, guild_id, database).await {
+            Ok(members) => {
+                for (user_id, profile_id) in members {
+                    // Opted out profiles stay with no channel, so their last pass keeps
+                    // moving and opting back in doesn't post old passes.
+                    let channels = audiences.entry(profile_id).or_default();
+                    if !opted_out.contains(&user_id) && !channels.contains(&channel_id) {
+                        channels.push(channel_id);
+                    }
+                }
+            }
+            Err(e) => log_message(
+                format!("Couldn't list the members of guild {}: {}", guild_id, e).as_str(),
+                LogLevel::Warning,
+            ),
+        }
+    }
+
+    for (profile_id, channels) in audiences {
+        let new_passes = match new_passes(profile_id, states).await {
+            Ok(passes) => passes,
+            Err(e) => {
+                log_message(
+                    format!("Couldn't check the passes of {profile_id}: {e}").as_str(),
+                    LogLevel::Warning,
+                );
+                continue;
+            }
+        };
+
+        if channels.is_empty() {
+            if let Some(&latest) = new_passes.last()
+                && let Err(e) = save_last_pass(profile_id, latest, states).await
+            {
+                log_message(
+                    format!("Couldn't save the last pass of {profile_id}: {e}").as_str(),
+                    LogLevel::Error,
+                );
+            }
+            continue;
+        }
+
+        for pass_id in new_passes {
+            // Stops at the first failure so the pass is tried again on the next check.
+            let clear = match get_clear_info(&pass_id).await {
+                Ok(clear) => clear,
+                Err(e) => {
+                    log_message(
+                        format!("Couldn't fetch pass {pass_id}: {e}").as_str(),
+                        LogLevel::Warning,
+                    );
+                    break;
+                }
+            };
+
+            let message = CreateMessage::new()
+                .content(highlights(&clear))
+                .embed(clear_embed(pass_id, clear));
+
+            for channel_id in &channels {
+                sleep(Duration::from_secs(1)).await; // Sleep for 1 second to avoid rate limits
+                if let Err(e) = channel_id.send_message(&ctx.http, message.clone()).await {
+                    log_message(
+                        format!("Failed to send pass {}: {:?}", pass_id, e).as_str(),
+                        LogLevel::Error,
+                    );
+                }
+            }
+
+            // Saved per pass, so a crash mid-check doesn't lose the passes not posted yet.
+            if let Err(e) = save_last_pass(profile_id, pass_id, states).await {
+                log_message(
+                    format!("Couldn't save the last pass of {profile_id}: {e}").as_str(),
+                    LogLevel::Error,
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The ids of the player's passes since the last check, oldest first. A player seen for
+/// the first time has no new passes and their latest pass is stored as the last seen one,
+/// so linking someone doesn't post their whole history.
+async fn new_passes(
+    profile_id: u64,
+    states: &Collection<Document>,
+) -> Result<Vec<u64>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut pass_ids = get_passes(profile_id, None)
+        .await?
+        .iter()
+        .map(|pass| pass.id)
+        .collect::<Vec<_>>();
+    pass_ids.sort();
+
+    let Some(&latest) = pass_ids.last() else {
+        return Ok(Vec::new());
+    };
+
+    let last_seen = states
+        .find_one(doc! { "_id": profile_id as i64 })
+        .await?
+        .and_then(|state| state.get_i64("last_pass_id").ok());
+
+    let Some(last_seen) = last_seen else {
+        save_last_pass(profile_id, latest, states).await?;
+        return Ok(Vec::new());
+    };
+
+    let new_passes = pass_ids
+        .into_iter()
+        .filter(|id| *id as i64 > last_seen)
+        .collect::<Vec<_>>();
+    let skip = new_passes.len().saturating_sub(MAX_PASSES_PER_CHECK);
+
+    Ok(new_passes.into_iter().skip(skip).collect())
+}
+
+/// Moves the player's last seen pass forward, once that pass has been posted.
+async fn save_last_pass(
+    profile_id: u64,
+    pass_id: u64,
+    states: &Collection<Document>,
+) -> Result<(), mongodb::error::Error> {
+    states
+        .replace_one(
+            doc! { "_id": profile_id as i64 },
+            doc! { "_id": profile_id as i64, "last_pass_id": pass_id as i64 },
+        )
+        .upsert(true)
+        .await?;
+
+    Ok(())
+}
+
+fn highlights(clear: &ClearInfo) -> String {
+    let mut highlights = vec![format!("🎉 New pass by **{}**", clear.player_name)];
+
+    if clear.is_worlds_first {
+        highlights.push("🏆 **World's First!**".to_string());
+    }
+    if clear.is_16k {
+        highlights.push("**16K**".to_string());
+    } else if clear.is_12k {
+        highlights.push("**12K**".to_string());
+    }
+    if clear.is_no_hold_tap {
+        highlights.push("**No Hold Tap**".to_string());
+    }
+
+    highlights.join(" | ")
+}
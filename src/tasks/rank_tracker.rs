@@ -0,0 +1,379 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use mongodb::{
+    Collection,
+    bson::{Document, doc},
+};
+use serenity::{
+    all::{ChannelId, Context, CreateEmbed, CreateMessage, GuildId, UserId},
+    futures::{StreamExt, TryStreamExt},
+};
+use tokio::{sync::Mutex, time::sleep};
+
+use crate::{
+    LogLevel,
+    cache_manager::CacheManager,
+    commands::server_leaderboard::linked_members,
+    database::Database,
+    log_message,
+    tuforums::{difficulty::DifficultyTier, profile::get_profile},
+};
+
+/// How often linked players are snapshotted.
+const INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Ranks a player has to move since the last announcement to be announced again.
+const RANK_THRESHOLD: i64 = 10;
+
+/// How many profiles are fetched from TUF at the same time.
+const CONCURRENT_FETCHES: usize = 8;
+
+/// Lines per announcement embed.
+const LINES_PER_EMBED: usize = 20;
+
+/// What the tracker remembers about a player, stored in `stat_snapshots` under the profile id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    /// Rank at the last announcement, so slow drifts add up until they pass the threshold.
+    pub rank: i64,
+    pub ranked_score: f64,
+    pub top_diff: String,
+}
+
+impl Snapshot {
+    fn from_doc(doc: &Document) -> Option<Snapshot> {
+        Some(Snapshot {
+            rank: doc.get_i64("rank").ok()?,
+            ranked_score: doc.get_f64("ranked_score").ok()?,
+            top_diff: doc.get_str("top_diff").ok()?.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatChange {
+    Rank { from: i64, to: i64 },
+    TopDiff { from: String, to: String },
+}
+
+/// The changes worth announcing between two snapshots, and the snapshot to store next.
+pub fn diff_snapshots(previous: &Snapshot, current: &Snapshot) -> (Vec<StatChange>, Snapshot) {
+    let mut changes = Vec::new();
+    let mut next = current.clone();
+
+    // Unranked players (rank 0) only get announced when they enter the leaderboard.
+    if current.rank != 0
+        && (previous.rank == 0 || (previous.rank - current.rank).abs() >= RANK_THRESHOLD)
+    {
+        changes.push(StatChange::Rank {
+            from: previous.rank,
+            to: current.rank,
+        });
+    } else if current.rank != 0 {
+        next.rank = previous.rank;
+    }
+
+    // Special tiers like Marathon aren't above or below anything, they never count.
+    let raised = match (
+        previous.top_diff.parse::<DifficultyTier>().ok(),
+        current.top_diff.parse::<DifficultyTier>().ok(),
+    ) {
+        (_, Some(current)) if current.is_special() => false,
+        (Some(previous), Some(current)) if !previous.is_special() => current > previous,
+        (_, current) => current.is_some(),
+    };
+
+    if raised {
+        changes.push(StatChange::TopDiff {
+            from: previous.top_diff.clone(),
+            to: current.top_diff.clone(),
+        });
+    }
+
+    (changes, next)
+}
+
+fn change_line(change: &StatChange, user_id: UserId, name: &str) -> String {
+    match change {
+        StatChange::Rank { from: 0, to } => {
+            format!("🆕 <@{user_id}> (**{name}**) entered the ranked leaderboard at **#{to}**")
+        }
+        StatChange::Rank { from, to } if to < from => {
+            format!("📈 <@{user_id}> (**{name}**) climbed from #{from} to **#{to}**")
+        }
+        StatChange::Rank { from, to } => {
+            format!("📉 <@{user_id}> (**{name}**) dropped from #{from} to **#{to}**")
+        }
+        StatChange::TopDiff { from, to } => {
+            format!("⭐ <@{user_id}> (**{name}**) has a new top difficulty: **{to}** (was {from})")
+        }
+    }
+}
+
+pub async fn run_task(
+    ctx: &Context,
+    database: &Database,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) {
+    log_message("Launching rank tracker task", LogLevel::Info);
+
+    let (Some(setups), Some(snapshots), Some(users)) = (
+        database.get_collection("onuzglorp-bot", "setups").await,
+        database
+            .get_collection("onuzglorp-bot", "stat_snapshots")
+            .await,
+        database.get_collection("onuzglorp-bot", "users").await,
+    ) else {
+        log_message(
+            "Database is not connected, rank tracker not started",
+            LogLevel::Warning,
+        );
+        return;
+    };
+
+    let ctx = ctx.clone();
+    let database = database.clone();
+    let cache_manager = cache_manager.clone();
+
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) =
+                track(&ctx, &database, &cache_manager, &setups, &snapshots, &users).await
+            {
+                log_message(
+                    format!("Rank tracker failed: {}", e).as_str(),
+                    LogLevel::Error,
+                );
+            }
+
+            sleep(INTERVAL).await;
+        }
+    });
+}
+
+async fn track(
+    ctx: &Context,
+    database: &Database,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+    setups: &Collection<Document>,
+    snapshots: &Collection<Document>,
+    users: &Collection<Document>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let channels = setups
+        .find(doc! { "type": "rank-updates-channel" })
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?
+        .iter()
+        .filter_map(|doc| {
+            Some((
+                GuildId::new(doc.get_str("guild_id").ok()?.parse().ok()?),
+                ChannelId::new(doc.get_str("channel_id").ok()?.parse().ok()?),
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    if channels.is_empty() {
+        return Ok(());
+    }
+
+    let opted_out = users
+        .find(doc! { "notify_rank_changes": false })
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?
+        .iter()
+        .filter_map(|doc| Some(UserId::new(doc.get_i64("_id").ok()? as u64)))
+        .collect::<HashSet<_>>();
+
+    // Who to announce each profile to, as `(channel, discord user)`.
+    let mut audiences = HashMap::<u64, Vec<(ChannelId, UserId)>>::new();
+    for (guild_id, channel_id) in channels {
+        match linked_members(ctx, guild_id, database).await {
+            Ok(members) => {
+                for (user_id, profile_id) in members {
+                    audiences
+                        .entry(profile_id)
+                        .or_default()
+                        .push((channel_id, user_id));
+                }
+            }
+            Err(e) => log_message(
+                format!("Couldn't list the members of guild {}: {}", guild_id, e).as_str(),
+                LogLevel::Warning,
+            ),
+        }
+    }
+
+    let changes = serenity::futures::stream::iter(audiences.keys().copied())
+        .map(|profile_id| async move {
+            match snapshot_profile(profile_id, snapshots, cache_manager).await {
+                Ok(changes) => Some((profile_id, changes)),
+                Err(e) => {
+                    log_message(
+                        format!("Couldn't snapshot profile {profile_id}: {e}").as_str(),
+                        LogLevel::Warning,
+                    );
+                    None
+                }
+            }
+        })
+        .buffer_unordered(CONCURRENT_FETCHES)
+        .filter_map(|changes| async move { changes })
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut lines = HashMap::<ChannelId, Vec<String>>::new();
+    for (profile_id, (name, profile_changes)) in changes {
+        for (channel_id, user_id) in &audiences[&profile_id] {
+            if opted_out.contains(user_id) {
+                continue;
+            }
+            lines.entry(*channel_id).or_default().extend(
+                profile_changes
+                    .iter()
+                    .map(|change| change_line(change, *user_id, &name)),
+            );
+        }
+    }
+
+    for (channel_id, lines) in lines {
+        for chunk in lines.chunks(LINES_PER_EMBED) {
+            let embed = CreateEmbed::new()
+                .title("Rank updates")
+                .description(chunk.join("\n"))
+                .color(0xFF69B4);
+
+            sleep(Duration::from_secs(1)).await; // Sleep for 1 second to avoid rate limits
+            if let Err(e) = channel_id
+                .send_message(&ctx.http, CreateMessage::new().embed(embed))
+                .await
+            {
+                log_message(
+                    format!("Failed to send rank updates: {:?}", e).as_str(),
+                    LogLevel::Error,
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Fetches the profile, stores its new snapshot and returns the player's name with
+/// what changed. The first snapshot of a player never announces anything.
+async fn snapshot_profile(
+    profile_id: u64,
+    snapshots: &Collection<Document>,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) -> Result<(String, Vec<StatChange>), Box<dyn std::error::Error + Send + Sync>> {
+    let (profile, _) = get_profile(profile_id, Some(cache_manager)).await?;
+
+    let current = Snapshot {
+        rank: profile.stats.rank.0,
+        ranked_score: profile.stats.ranked_score,
+        top_diff: profile.stats.top_diff.name.clone(),
+    };
+
+    let previous = snapshots
+        .find_one(doc! { "_id": profile_id as i64 })
+        .await?
+        .as_ref()
+        .and_then(Snapshot::from_doc);
+
+    let (changes, next) = match previous {
+        Some(previous) => diff_snapshots(&previous, &current),
+        None => (Vec::new(), current),
+    };
+
+    snapshots
+        .replace_one(
+            doc! { "_id": profile_id as i64 },
+            doc! {
+                "_id": profile_id as i64,
+                "rank": next.rank,
+                "ranked_score": next.ranked_score,
+                "top_diff": next.top_diff,
+                "updated_at": mongodb::bson::DateTime::now(),
+            },
+        )
+        .upsert(true)
+        .await?;
+
+    Ok((profile.name, changes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(rank: i64, top_diff: &str) -> Snapshot {
+        Snapshot {
+            rank,
+            ranked_score: 0.,
+            top_diff: top_diff.to_string(),
+        }
+    }
+
+    #[test]
+    fn small_moves_stay_below_the_threshold() {
+        let (changes, next) = diff_snapshots(&snapshot(100, "G5"), &snapshot(95, "G5"));
+        assert!(changes.is_empty());
+        assert_eq!(next.rank, 100);
+
+        let (changes, next) = diff_snapshots(&snapshot(100, "G5"), &snapshot(90, "G5"));
+        assert_eq!(changes, vec![StatChange::Rank { from: 100, to: 90 }]);
+        assert_eq!(next.rank, 90);
+
+        let (changes, _) = diff_snapshots(&snapshot(90, "G5"), &snapshot(100, "G5"));
+        assert_eq!(changes, vec![StatChange::Rank { from: 90, to: 100 }]);
+    }
+
+    #[test]
+    fn drift_accumulates_until_the_threshold() {
+        let mut stored = snapshot(100, "G5");
+        for rank in [97, 94, 92] {
+            let (changes, next) = diff_snapshots(&stored, &snapshot(rank, "G5"));
+            assert!(changes.is_empty());
+            stored = next;
+        }
+
+        let (changes, next) = diff_snapshots(&stored, &snapshot(89, "G5"));
+        assert_eq!(changes, vec![StatChange::Rank { from: 100, to: 89 }]);
+        assert_eq!(next.rank, 89);
+    }
+
+    #[test]
+    fn leaving_the_leaderboard_is_silent() {
+        let (changes, next) = diff_snapshots(&snapshot(50, "G5"), &snapshot(0, "G5"));
+        assert!(changes.is_empty());
+        assert_eq!(next.rank, 0);
+
+        let (changes, _) = diff_snapshots(&next, &snapshot(48, "G5"));
+        assert_eq!(changes, vec![StatChange::Rank { from: 0, to: 48 }]);
+    }
+
+    #[test]
+    fn only_a_higher_top_diff_is_announced() {
+        let raised = |from: &str, to: &str| {
+            let (changes, _) = diff_snapshots(&snapshot(1, from), &snapshot(1, to));
+            changes.contains(&StatChange::TopDiff {
+                from: from.to_string(),
+                to: to.to_string(),
+            })
+        };
+
+        assert!(raised("G5", "G6"));
+        assert!(raised("G20", "U1"));
+        assert!(!raised("G5", "G5"));
+        assert!(!raised("U1", "G20"));
+        assert!(raised("", "P3"));
+        assert!(raised("Marathon", "G1"));
+        assert!(!raised("G5", "Marathon"));
+        assert!(!raised("G5", "not a tier"));
+    }
+}
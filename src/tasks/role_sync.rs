@@ -0,0 +1,454 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use mongodb::bson::doc;
+use serenity::{
+    all::{Context, GuildId, Member, RoleId, UserId},
+    futures::{StreamExt, TryStreamExt},
+};
+use tokio::{sync::Mutex, time::sleep};
+
+use crate::{
+    LogLevel,
+    cache_manager::CacheManager,
+    commands::server_leaderboard::linked_profiles,
+    database::Database,
+    log_message,
+    tuforums::{
+        difficulty::DifficultyRange,
+        profile::{Profile, get_profile},
+    },
+};
+
+/// How often every guild with role rules is synced.
+const INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// How many profiles are fetched from TUF at the same time.
+const CONCURRENT_FETCHES: usize = 8;
+
+const AUDIT_LOG_REASON: &str = "TUF role sync";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleCondition {
+    /// The profile's top difficulty is in the range.
+    TopDiff(DifficultyRange),
+    /// The ranked leaderboard position, unranked players never match.
+    Rank { min: u32, max: u32 },
+}
+
+/// A Discord role given to the linked members matching it, stored in `role_rules`.
+#[derive(Debug, Clone)]
+pub struct RoleRule {
+    pub role_id: RoleId,
+    pub condition: RuleCondition,
+    /// The rule in its normalized form, like `diff:U1-U20` or `rank:1-100`.
+    pub text: String,
+}
+
+impl RoleRule {
+    /// Parses `diff:G1-U20` or `rank:1-100`, a single value like `rank:1` works too.
+    pub fn parse(role_id: RoleId, text: &str) -> Option<RoleRule> {
+        let (kind, range) = text.split_once(':')?;
+
+        let (condition, text) = match kind.trim().to_lowercase().as_str() {
+            "diff" | "difficulty" => {
+                let range = range.parse::<DifficultyRange>().ok()?;
+                let text = format!("diff:{}", range);
+                (RuleCondition::TopDiff(range), text)
+            }
+            "rank" => {
+                let (min, max) = range.split_once('-').unwrap_or((range, range));
+                let (min, max) = (min.trim().parse::<u32>().ok()?, max.trim().parse().ok()?);
+                let (min, max) = (min.min(max).max(1), min.max(max));
+                // `rank:0` would never match, ranks start at 1.
+                if max < min {
+                    return None;
+                }
+                let text = if min == max {
+                    format!("rank:{}", min)
+                } else {
+                    format!("rank:{}-{}", min, max)
+                };
+                (RuleCondition::Rank { min, max }, text)
+            }
+            _ => return None,
+        };
+
+        Some(RoleRule {
+            role_id,
+            condition,
+            text,
+        })
+    }
+
+    fn matches(&self, profile: &Profile) -> bool {
+        match &self.condition {
+            RuleCondition::TopDiff(range) => profile
+                .stats
+                .top_diff
+                .tier()
+                .is_some_and(|tier| range.contains(&tier)),
+            RuleCondition::Rank { min, max } => u32::try_from(profile.stats.rank.0)
+                .is_ok_and(|rank| rank > 0 && (*min..=*max).contains(&rank)),
+        }
+    }
+}
+
+pub async fn guild_rules(
+    guild_id: GuildId,
+    database: &Database,
+) -> Result<Vec<RoleRule>, Box<dyn std::error::Error + Send + Sync>> {
+    let collection = database
+        .get_collection("onuzglorp-bot", "role_rules")
+        .await
+        .ok_or("Database is not connected")?;
+
+    let rules = collection
+        .find(doc! { "guild_id": guild_id.get().to_string() })
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?
+        .iter()
+        .filter_map(|rule| {
+            let role_id = RoleId::new(rule.get_str("role_id").ok()?.parse().ok()?);
+            RoleRule::parse(role_id, rule.get_str("rule").ok()?)
+        })
+        .collect();
+
+    Ok(rules)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RoleChange {
+    pub user_id: UserId,
+    pub role_id: RoleId,
+    pub add: bool,
+}
+
+#[derive(Default)]
+pub struct RolePlan {
+    pub changes: Vec<RoleChange>,
+    /// Roles in the rules the bot can't give, because they are above its highest role.
+    pub unmanageable: Vec<RoleId>,
+}
+
+impl RolePlan {
+    /// One line per change, for previews and reports.
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = self
+            .unmanageable
+            .iter()
+            .map(|role_id| {
+                format!("⚠️ <@&{role_id}> is above the bot's highest role, it won't be synced")
+            })
+            .collect::<Vec<_>>();
+
+        lines.extend(self.changes.iter().map(|change| {
+            format!(
+                "{} <@&{}> {} <@{}>",
+                if change.add { "➕ add" } else { "➖ remove" },
+                change.role_id,
+                if change.add { "to" } else { "from" },
+                change.user_id
+            )
+        }));
+
+        lines
+    }
+}
+
+/// The role changes making every member of the guild match the rules.
+pub async fn plan_guild(
+    ctx: &Context,
+    guild_id: GuildId,
+    database: &Database,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) -> Result<RolePlan, Box<dyn std::error::Error + Send + Sync>> {
+    let members = guild_id
+        .members_iter(&ctx.http)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    plan(ctx, guild_id, members, database, cache_manager).await
+}
+
+/// Same as [`plan_guild`] for a single member, used when someone links a profile.
+pub async fn plan_member(
+    ctx: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+    database: &Database,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) -> Result<RolePlan, Box<dyn std::error::Error + Send + Sync>> {
+    let member = guild_id.member(&ctx.http, user_id).await?;
+
+    plan(ctx, guild_id, vec![member], database, cache_manager).await
+}
+
+async fn plan(
+    ctx: &Context,
+    guild_id: GuildId,
+    members: Vec<Member>,
+    database: &Database,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) -> Result<RolePlan, Box<dyn std::error::Error + Send + Sync>> {
+    let rules = guild_rules(guild_id, database).await?;
+    if rules.is_empty() {
+        return Ok(RolePlan::default());
+    }
+
+    // Discord refuses to give roles at or above the bot's highest role.
+    let guild = guild_id.to_partial_guild(&ctx.http).await?;
+    let bot_id = ctx.cache.current_user().id;
+    let bot = guild_id.member(&ctx.http, bot_id).await?;
+    let bot_position = bot
+        .roles
+        .iter()
+        .filter_map(|role_id| guild.roles.get(role_id))
+        .map(|role| role.position)
+        .max()
+        .unwrap_or(0);
+
+    let mut unmanageable = Vec::new();
+    let mut managed = HashSet::new();
+    for rule in &rules {
+        match guild.roles.get(&rule.role_id) {
+            Some(role) if role.position < bot_position && !role.managed => {
+                managed.insert(rule.role_id);
+            }
+            Some(_) if !unmanageable.contains(&rule.role_id) => unmanageable.push(rule.role_id),
+            _ => {}
+        }
+    }
+
+    let user_ids = members
+        .iter()
+        .map(|member| member.user.id)
+        .collect::<Vec<_>>();
+    let links = linked_profiles(&user_ids, database)
+        .await?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+    // Members whose profile couldn't be fetched keep their roles until the next sync.
+    let profiles = serenity::futures::stream::iter(links.values().copied().collect::<HashSet<_>>())
+        .map(|profile_id| async move {
+            (
+                profile_id,
+                get_profile(profile_id, Some(cache_manager))
+                    .await
+                    .map(|(profile, _)| profile),
+            )
+        })
+        .buffer_unordered(CONCURRENT_FETCHES)
+        .filter_map(|(profile_id, profile)| async move {
+            match profile {
+                Ok(profile) => Some((profile_id, profile)),
+                Err(e) => {
+                    log_message(
+                        format!("Couldn't fetch profile {profile_id}: {e}").as_str(),
+                        LogLevel::Warning,
+                    );
+                    None
+                }
+            }
+        })
+        .collect::<HashMap<_, _>>()
+        .await;
+
+    let mut changes = Vec::new();
+    for member in &members {
+        let wanted = match links.get(&member.user.id) {
+            Some(profile_id) => match profiles.get(profile_id) {
+                Some(profile) => rules
+                    .iter()
+                    .filter(|rule| rule.matches(profile))
+                    .map(|rule| rule.role_id)
+                    .collect::<HashSet<_>>(),
+                None => continue,
+            },
+            // Members who unlinked lose the synced roles.
+            None => HashSet::new(),
+        };
+
+        for role_id in &managed {
+            let has = member.roles.contains(role_id);
+            let wants = wanted.contains(role_id);
+            if has != wants {
+                changes.push(RoleChange {
+                    user_id: member.user.id,
+                    role_id: *role_id,
+                    add: wants,
+                });
+            }
+        }
+    }
+
+    Ok(RolePlan {
+        changes,
+        unmanageable,
+    })
+}
+
+/// Applies the plan and returns a line for each change Discord refused.
+pub async fn apply(ctx: &Context, guild_id: GuildId, plan: &RolePlan) -> Vec<String> {
+    let mut failures = Vec::new();
+
+    for change in &plan.changes {
+        let result = if change.add {
+            ctx.http
+                .add_member_role(
+                    guild_id,
+                    change.user_id,
+                    change.role_id,
+                    Some(AUDIT_LOG_REASON),
+                )
+                .await
+        } else {
+            ctx.http
+                .remove_member_role(
+                    guild_id,
+                    change.user_id,
+                    change.role_id,
+                    Some(AUDIT_LOG_REASON),
+                )
+                .await
+        };
+
+        if let Err(e) = result {
+            let reason = match &e {
+                serenity::Error::Http(http)
+                    if http.status_code().map(|status| status.as_u16()) == Some(403) =>
+                {
+                    "missing permissions or role hierarchy".to_string()
+                }
+                e => e.to_string(),
+            };
+            log_message(
+                format!(
+                    "Couldn't sync role {} of {} in guild {}: {}",
+                    change.role_id, change.user_id, guild_id, reason
+                )
+                .as_str(),
+                LogLevel::Warning,
+            );
+            failures.push(format!(
+                "❌ <@&{}> for <@{}>: {}",
+                change.role_id, change.user_id, reason
+            ));
+        }
+    }
+
+    failures
+}
+
+pub async fn run_task(
+    ctx: &Context,
+    database: &Database,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) {
+    log_message("Launching role sync task", LogLevel::Info);
+
+    let Some(collection) = database.get_collection("onuzglorp-bot", "role_rules").await else {
+        log_message(
+            "Database is not connected, role sync not started",
+            LogLevel::Warning,
+        );
+        return;
+    };
+
+    let ctx = ctx.clone();
+    let database = database.clone();
+    let cache_manager = cache_manager.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let guild_ids = match collection.distinct("guild_id", doc! {}).await {
+                Ok(guild_ids) => guild_ids
+                    .iter()
+                    .filter_map(|guild_id| guild_id.as_str()?.parse().ok())
+                    .map(GuildId::new)
+                    .collect::<Vec<_>>(),
+                Err(e) => {
+                    log_message(
+                        format!("Couldn't load role rules: {}", e).as_str(),
+                        LogLevel::Error,
+                    );
+                    Vec::new()
+                }
+            };
+
+            for guild_id in guild_ids {
+                match plan_guild(&ctx, guild_id, &database, &cache_manager).await {
+                    Ok(plan) => {
+                        apply(&ctx, guild_id, &plan).await;
+                    }
+                    Err(e) => log_message(
+                        format!("Role sync failed for guild {}: {}", guild_id, e).as_str(),
+                        LogLevel::Warning,
+                    ),
+                }
+            }
+
+            sleep(INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tuforums::difficulty::DifficultyTier;
+
+    fn parse(text: &str) -> Option<RoleRule> {
+        RoleRule::parse(RoleId::new(1), text)
+    }
+
+    #[test]
+    fn parses_difficulty_rules() {
+        let rule = parse("diff:g1-u20").unwrap();
+        assert_eq!(rule.text, "diff:G1-U20");
+        match rule.condition {
+            RuleCondition::TopDiff(range) => {
+                assert_eq!(range.min, DifficultyTier::G(1));
+                assert_eq!(range.max, DifficultyTier::U(20));
+            }
+            condition => panic!("expected a difficulty rule, got {:?}", condition),
+        }
+
+        assert_eq!(parse("Difficulty: U5").unwrap().text, "diff:U5");
+        assert!(parse("diff:Z3").is_none());
+    }
+
+    #[test]
+    fn parses_rank_rules() {
+        let rule = parse("rank:1-100").unwrap();
+        assert_eq!(rule.condition, RuleCondition::Rank { min: 1, max: 100 });
+        assert_eq!(rule.text, "rank:1-100");
+
+        let rule = parse("rank: 100 - 1").unwrap();
+        assert_eq!(rule.condition, RuleCondition::Rank { min: 1, max: 100 });
+
+        let rule = parse("RANK:5").unwrap();
+        assert_eq!(rule.condition, RuleCondition::Rank { min: 5, max: 5 });
+        assert_eq!(rule.text, "rank:5");
+
+        assert_eq!(
+            parse("rank:0-10").unwrap().condition,
+            RuleCondition::Rank { min: 1, max: 10 }
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_rules() {
+        assert!(parse("rank:first").is_none());
+        assert!(parse("rank:-5").is_none());
+        assert!(parse("passes:10").is_none());
+        assert!(parse("rank").is_none());
+        assert!(parse("rank:0").is_none());
+        assert!(parse("rank:0-0").is_none());
+    }
+}
@@ -0,0 +1,125 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{Datelike, Duration as ChronoDuration};
+use serenity::{
+    all::{ChannelId, Context, CreateMessage, GuildId},
+    futures::TryStreamExt,
+};
+use tokio::{sync::Mutex, time::sleep};
+
+use crate::{
+    LogLevel,
+    cache_manager::CacheManager,
+    commands::server_leaderboard::{ServerSort, get_server_standings, server_embed},
+    database::Database,
+    log_message,
+};
+
+/// Members shown in the digest.
+const DIGEST_LIMIT: usize = 10;
+
+pub async fn run_task(
+    ctx: &Context,
+    database: &Database,
+    cache_manager: &Arc<Mutex<CacheManager>>,
+) {
+    log_message("Launching weekly server digest task", LogLevel::Info);
+
+    let Some(collection) = database.get_collection("onuzglorp-bot", "setups").await else {
+        log_message(
+            "Database is not connected, weekly digest not started",
+            LogLevel::Warning,
+        );
+        return;
+    };
+
+    let ctx = ctx.clone();
+    let database = database.clone();
+    let cache_manager = cache_manager.clone();
+
+    tokio::spawn(async move {
+        loop {
+            let now = chrono::Utc::now();
+            let days_until_monday = 7 - now.weekday().num_days_from_monday() as i64;
+            let next_monday = (now + ChronoDuration::days(days_until_monday))
+                .date_naive()
+                .and_hms_opt(0, 0, 0)
+                .unwrap();
+            let duration_until_monday = (next_monday - now.naive_utc()).to_std().unwrap();
+
+            log_message(
+                format!(
+                    "Sleeping for {:?} until the weekly digest",
+                    duration_until_monday
+                )
+                .as_str(),
+                LogLevel::Info,
+            );
+            sleep(duration_until_monday).await;
+
+            let filter = mongodb::bson::doc! { "type": "weekly-digest-channel" };
+            let results: Vec<_> = match collection.find(filter).await {
+                Ok(cursor) => cursor.try_collect().await.unwrap_or_default(),
+                Err(e) => {
+                    log_message(
+                        format!("Failed to find digest channels: {}", e).as_str(),
+                        LogLevel::Error,
+                    );
+                    continue;
+                }
+            };
+
+            for doc in results {
+                let (Some(guild_id), Some(channel_id)) = (
+                    doc.get_str("guild_id")
+                        .ok()
+                        .and_then(|id| id.parse::<u64>().ok()),
+                    doc.get_str("channel_id")
+                        .ok()
+                        .and_then(|id| id.parse::<u64>().ok()),
+                ) else {
+                    continue;
+                };
+
+                let standings = match get_server_standings(
+                    &ctx,
+                    GuildId::new(guild_id),
+                    &database,
+                    &cache_manager,
+                    ServerSort::Ranked,
+                )
+                .await
+                {
+                    Ok(standings) => standings,
+                    Err(e) => {
+                        log_message(
+                            format!("Failed to build the digest for guild {}: {}", guild_id, e)
+                                .as_str(),
+                            LogLevel::Warning,
+                        );
+                        continue;
+                    }
+                };
+
+                let message = CreateMessage::new().embed(server_embed(
+                    "Weekly server digest",
+                    &standings,
+                    ServerSort::Ranked,
+                    DIGEST_LIMIT,
+                    None,
+                ));
+
+                sleep(Duration::from_secs(1)).await; // Sleep for 1 second to avoid rate limits
+                if let Err(e) = ChannelId::new(channel_id)
+                    .send_message(&ctx.http, message)
+                    .await
+                {
+                    log_message(
+                        format!("Failed to send the weekly digest: {:?}", e).as_str(),
+                        LogLevel::Error,
+                    );
+                }
+            }
+        }
+    });
+}
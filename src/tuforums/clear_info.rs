@@ -1,5 +1,9 @@
+use tokio::sync::Mutex;
+
+use crate::cache_manager::{CacheManager, LiveTime};
+
 use super::{
-    difficulty::{Difficulty, convert_from_hex_to_rgb},
+    difficulty::{Difficulty, convert_from_hex_to_rgb, difficulty_name},
     level::Level,
 };
 
@@ -21,6 +25,7 @@ pub struct ClearInfo {
     pub is_no_miss: bool,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Judgements(
     pub u64,
     pub u64,
@@ -31,14 +36,214 @@ pub struct Judgements(
     pub u64,
 );
 
+impl Judgements {
+    /// The number of tiles in the level. Too early hits aren't on a tile, like on TUF
+    /// they only count as misses.
+    pub fn tile_count(&self) -> u64 {
+        self.1 + self.2 + self.3 + self.4 + self.5 + self.6
+    }
+
+    /// Misses are the "too early" hits.
+    pub fn misses(&self) -> u64 {
+        self.0
+    }
+}
+
+/// A clear as listed on a level's page.
+#[derive(Debug, Clone)]
+pub struct LevelPass {
+    pub id: u64,
+    pub player_name: String,
+    pub score: f64,
+    pub x_acc: f64,
+    pub speed: f64,
+    pub date: String,
+}
+
+/// Every clear of a level, best score first.
+pub async fn get_level_passes(
+    level_id: u32,
+    cache_manager: Option<&Mutex<CacheManager>>,
+) -> Result<Vec<LevelPass>, Box<dyn std::error::Error + Sync + Send>> {
+    if let Some(cache) = cache_manager
+        && let Some(passes) = cache
+            .lock()
+            .await
+            .get::<Vec<LevelPass>>(format!("level_passes_{level_id}").as_str())
+    {
+        return Ok(passes.clone());
+    }
+
+    let response = reqwest::get(format!(
+        "https://api.tuforums.com/v2/database/passes/level/{level_id}"
+    ))
+    .await?;
+
+    if !response.status().is_success() {
+        return Err(format!("Request failed with status: {}", response.status()).into());
+    }
+
+    let json: serde_json::Value = response.json().await?;
+
+    let mut passes = json
+        .as_array()
+        .or_else(|| json["results"].as_array())
+        .ok_or("Missing or invalid passes in JSON")?
+        .iter()
+        .filter(|pass| !pass["isDeleted"].as_bool().unwrap_or(false))
+        .map(|pass| LevelPass {
+            id: pass["id"].as_u64().unwrap_or(0),
+            player_name: pass["player"]["name"]
+                .as_str()
+                .unwrap_or("unknown")
+                .to_string(),
+            score: pass["scoreV2"].as_f64().unwrap_or(0.),
+            x_acc: pass["accuracy"]
+                .as_f64()
+                .or_else(|| pass["judgements"]["accuracy"].as_f64())
+                .unwrap_or(0.)
+                * 100.,
+            speed: pass["speed"]
+                .as_f64()
+                .filter(|speed| *speed > 0.)
+                .unwrap_or(1.),
+            date: chrono::DateTime::parse_from_rfc3339(
+                pass["vidUploadTime"].as_str().unwrap_or("unknown"),
+            )
+            .map(|dt| dt.format("%b %d, %Y").to_string())
+            .unwrap_or_else(|_| "unknown".to_string()),
+        })
+        .collect::<Vec<_>>();
+
+    passes.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    if let Some(cache) = cache_manager {
+        cache.lock().await.add(
+            format!("level_passes_{level_id}"),
+            passes.clone(),
+            Some(LiveTime::Minutes(5)),
+        );
+    }
+
+    Ok(passes)
+}
+
+#[derive(Clone)]
+pub struct ClearSearchResult {
+    pub id: u64,
+    pub player_name: String,
+    pub title: String,
+    pub artist: String,
+}
+
+pub async fn search_clears(
+    query: &str,
+    limit: u32,
+) -> Result<Vec<ClearSearchResult>, Box<dyn std::error::Error + Sync + Send>> {
+    let response = reqwest::Client::new()
+        .get("https://api.tuforums.com/v2/database/passes")
+        .query(&[
+            ("query", query),
+            ("limit", &limit.to_string()),
+            ("offset", "0"),
+            ("deletedFilter", "hide"),
+        ])
+        .send()
+        .await?;
+
+    let json: serde_json::Value = response.json().await?;
+
+    let clears = json["results"]
+        .as_array()
+        .ok_or("Missing or invalid 'results' field in JSON")?
+        .iter()
+        .filter_map(|clear| {
+            Some(ClearSearchResult {
+                id: clear["id"].as_u64()?,
+                player_name: clear["player"]["name"]
+                    .as_str()
+                    .unwrap_or("unknown")
+                    .to_string(),
+                title: clear["level"]["song"]
+                    .as_str()
+                    .unwrap_or("unknown")
+                    .to_string(),
+                artist: clear["level"]["artist"]
+                    .as_str()
+                    .unwrap_or("unknown")
+                    .to_string(),
+            })
+        })
+        .collect();
+
+    Ok(clears)
+}
+
+/// A pass from the TUF-wide list of recent passes.
+pub struct RecentPass {
+    pub id: u64,
+    pub player_name: String,
+    pub level_title: String,
+    pub level_artist: String,
+    pub difficulty: String,
+    pub is_worlds_first: bool,
+}
+
+/// The latest passes on TUF, newest first.
+pub async fn get_recent_passes(
+    limit: u32,
+) -> Result<Vec<RecentPass>, Box<dyn std::error::Error + Sync + Send>> {
+    let response = reqwest::Client::new()
+        .get("https://api.tuforums.com/v2/database/passes")
+        .query(&[
+            ("query", ""),
+            ("sort", "RECENT_DESC"),
+            ("limit", &limit.to_string()),
+            ("offset", "0"),
+            ("deletedFilter", "hide"),
+        ])
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(format!("Request failed with status: {}", response.status()).into());
+    }
+
+    let json: serde_json::Value = response.json().await?;
+
+    let passes = json["results"]
+        .as_array()
+        .ok_or("Missing or invalid 'results' field in JSON")?
+        .iter()
+        .filter_map(|pass| {
+            let level = &pass["level"];
+            Some(RecentPass {
+                id: pass["id"].as_u64()?,
+                player_name: pass["player"]["name"]
+                    .as_str()
+                    .unwrap_or("unknown")
+                    .to_string(),
+                level_title: level["song"].as_str().unwrap_or("unknown").to_string(),
+                level_artist: level["artist"].as_str().unwrap_or("unknown").to_string(),
+                difficulty: level["difficulty"]["name"]
+                    .as_str()
+                    .unwrap_or("?")
+                    .to_string(),
+                is_worlds_first: pass["isWorldsFirst"].as_bool().unwrap_or(false),
+            })
+        })
+        .collect();
+
+    Ok(passes)
+}
+
 pub async fn get_clear_info(
     id: &u64,
 ) -> Result<ClearInfo, Box<dyn std::error::Error + Sync + Send>> {
-    let response = reqwest::get(format!("https://api.tuforums.com/v2/database/passes/{id}"))
-        .await
-        .expect("Failed to send the request");
+    let response =
+        reqwest::get(format!("https://api.tuforums.com/v2/database/passes/{id}")).await?;
 
-    let json: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    let json: serde_json::Value = response.json().await?;
 
     if !json["error"].is_null() {
         return Err("Clear not found".into());
@@ -89,7 +294,7 @@ pub async fn get_clear_info(
         artist: beatmap["artist"].as_str().unwrap_or("none").to_string(),
         creator: beatmap["creator"].as_str().unwrap_or("none").to_string(),
         difficulty: Difficulty {
-            name: "".to_string(),
+            name: difficulty_name(&beatmap["difficulty"], beatmap["diffId"].as_u64()).await,
             icon: beatmap["difficulty"]["icon"]
                 .as_str()
                 .unwrap_or("none")
@@ -108,6 +313,10 @@ pub async fn get_clear_info(
         first_clear: "".to_string(),
         dl_link: "".to_string(),
         vido_link: "".to_string(),
+        tile_count: beatmap["tileCount"]
+            .as_u64()
+            .filter(|tile_count| *tile_count > 0)
+            .map(|tile_count| tile_count as u32),
     };
 
     let clear_info = ClearInfo {
@@ -132,5 +341,5 @@ pub async fn get_clear_info(
 }
 
 fn is_no_miss(judgement: &Judgements) -> bool {
-    judgement.0 == 0
+    judgement.misses() == 0
 }
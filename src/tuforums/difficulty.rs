@@ -1,3 +1,13 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::{Arc, LazyLock},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::Mutex;
+
 #[derive(Debug, Clone)]
 pub struct Difficulty {
     pub name: String,
@@ -6,6 +16,18 @@ pub struct Difficulty {
     pub score_base: f64,
 }
 
+impl Difficulty {
+    /// The tier named by this difficulty, `None` for names TUF added after this list.
+    pub fn tier(&self) -> Option<DifficultyTier> {
+        self.name.parse().ok()
+    }
+
+    /// Position on the PGU scale, see [`DifficultyTier::order`].
+    pub fn order(&self) -> Option<u32> {
+        self.tier()?.order()
+    }
+}
+
 pub fn convert_from_hex_to_rgb(hex: &str) -> (u8, u8, u8) {
     let hex = hex.trim_start_matches('#');
     let r = u8::from_str_radix(&hex[0..2], 16).unwrap_or(0);
@@ -13,3 +35,273 @@ pub fn convert_from_hex_to_rgb(hex: &str) -> (u8, u8, u8) {
     let b = u8::from_str_radix(&hex[4..6], 16).unwrap_or(0);
     (r, g, b)
 }
+
+/// Special tiers that are off the PGU scale but still count as levels.
+pub const SPECIAL_TIERS: [&str; 2] = ["Marathon", "Gimmick"];
+
+/// A TUF difficulty by name. Tiers are ordered from P1 up to U20 then Qq,
+/// special tiers come after all of them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DifficultyTier {
+    P(u8),
+    G(u8),
+    U(u8),
+    Qq,
+    /// One of the [`SPECIAL_TIERS`].
+    Special(String),
+}
+
+impl DifficultyTier {
+    /// Position on the PGU scale, P1 is 0 and Qq comes right after U20.
+    /// Special tiers aren't on it.
+    pub fn order(&self) -> Option<u32> {
+        match self {
+            DifficultyTier::P(level) => Some(*level as u32 - 1),
+            DifficultyTier::G(level) => Some(20 + *level as u32 - 1),
+            DifficultyTier::U(level) => Some(40 + *level as u32 - 1),
+            DifficultyTier::Qq => Some(60),
+            DifficultyTier::Special(_) => None,
+        }
+    }
+
+    pub fn is_special(&self) -> bool {
+        matches!(self, DifficultyTier::Special(_))
+    }
+}
+
+impl FromStr for DifficultyTier {
+    type Err = String;
+
+    /// Parses names like `P5`, `g13`, `U20`, `Qq` or `Marathon`, ignoring case.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let name = name.trim();
+
+        if name.eq_ignore_ascii_case("qq") {
+            return Ok(DifficultyTier::Qq);
+        }
+
+        if let Some(special) = SPECIAL_TIERS
+            .iter()
+            .find(|special| special.eq_ignore_ascii_case(name))
+        {
+            return Ok(DifficultyTier::Special(special.to_string()));
+        }
+
+        let mut chars = name.chars();
+        let tier = chars.next().map(|tier| tier.to_ascii_uppercase());
+        let level = chars
+            .as_str()
+            .parse::<u8>()
+            .ok()
+            .filter(|level| (1..=20).contains(level));
+
+        match (tier, level) {
+            (Some('P'), Some(level)) => Ok(DifficultyTier::P(level)),
+            (Some('G'), Some(level)) => Ok(DifficultyTier::G(level)),
+            (Some('U'), Some(level)) => Ok(DifficultyTier::U(level)),
+            _ => Err(format!("`{}` isn't a difficulty like G13 or U5", name)),
+        }
+    }
+}
+
+impl fmt::Display for DifficultyTier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DifficultyTier::P(level) => write!(f, "P{}", level),
+            DifficultyTier::G(level) => write!(f, "G{}", level),
+            DifficultyTier::U(level) => write!(f, "U{}", level),
+            DifficultyTier::Qq => write!(f, "Qq"),
+            DifficultyTier::Special(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// An inclusive range of tiers on the PGU scale, like `G1-U20`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DifficultyRange {
+    pub min: DifficultyTier,
+    pub max: DifficultyTier,
+}
+
+impl DifficultyRange {
+    /// Every PGU tier, what TUF searches when no range is given.
+    pub const ALL_PGU: DifficultyRange = DifficultyRange {
+        min: DifficultyTier::P(1),
+        max: DifficultyTier::U(20),
+    };
+
+    /// Bounds given the wrong way around are swapped, special tiers can't be bounds.
+    pub fn new(min: DifficultyTier, max: DifficultyTier) -> Result<Self, String> {
+        if let Some(special) = [&min, &max].into_iter().find(|tier| tier.is_special()) {
+            return Err(format!("`{}` can't be used in a difficulty range", special));
+        }
+
+        Ok(if min <= max {
+            DifficultyRange { min, max }
+        } else {
+            DifficultyRange { min: max, max: min }
+        })
+    }
+
+    pub fn contains(&self, tier: &DifficultyTier) -> bool {
+        !tier.is_special() && self.min <= *tier && *tier <= self.max
+    }
+
+    /// The range as TUF's `pguRange` parameter.
+    pub fn pgu_param(&self) -> String {
+        format!("{},{}", self.min, self.max)
+    }
+}
+
+impl FromStr for DifficultyRange {
+    type Err = String;
+
+    /// Parses `G1-U20`, a single tier like `U5` is a range of one.
+    fn from_str(range: &str) -> Result<Self, Self::Err> {
+        let (min, max) = range.split_once('-').unwrap_or((range, range));
+
+        DifficultyRange::new(min.parse()?, max.parse()?)
+    }
+}
+
+impl fmt::Display for DifficultyRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.min == self.max {
+            write!(f, "{}", self.min)
+        } else {
+            write!(f, "{}-{}", self.min, self.max)
+        }
+    }
+}
+
+/// How long the difficulty catalog is kept before being fetched again.
+const CATALOG_LIFETIME: Duration = Duration::from_secs(60 * 60);
+
+type Catalog = Arc<HashMap<u64, Difficulty>>;
+
+static CATALOG: LazyLock<Mutex<Option<(Instant, Catalog)>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Every difficulty on TUF by id. Levels and passes only carry some fields of their
+/// difficulty, the catalog fills in the rest.
+pub async fn get_difficulty_catalog() -> Result<Catalog, Box<dyn std::error::Error + Send + Sync>> {
+    let mut catalog = CATALOG.lock().await;
+    if let Some((fetched, difficulties)) = catalog.as_ref()
+        && fetched.elapsed() < CATALOG_LIFETIME
+    {
+        return Ok(difficulties.clone());
+    }
+
+    let response = reqwest::get("https://api.tuforums.com/v2/diffs").await?;
+
+    if !response.status().is_success() {
+        return Err(format!("Request failed with status: {}", response.status()).into());
+    }
+
+    let json: serde_json::Value = response.json().await?;
+
+    let difficulties = Arc::new(
+        json.as_array()
+            .ok_or("Missing or invalid difficulties in JSON")?
+            .iter()
+            .filter_map(|difficulty| {
+                Some((
+                    difficulty["id"].as_u64()?,
+                    Difficulty {
+                        name: difficulty["name"].as_str()?.to_string(),
+                        icon: difficulty["icon"].as_str().unwrap_or("").to_string(),
+                        color: convert_from_hex_to_rgb(
+                            difficulty["color"].as_str().unwrap_or("#000000"),
+                        ),
+                        score_base: difficulty["baseScore"].as_f64().unwrap_or(0.),
+                    },
+                ))
+            })
+            .collect::<HashMap<_, _>>(),
+    );
+
+    *catalog = Some((Instant::now(), difficulties.clone()));
+
+    Ok(difficulties)
+}
+
+/// The name of a difficulty JSON object, looked up in the catalog by id or icon when
+/// TUF left it out.
+pub async fn difficulty_name(difficulty: &serde_json::Value, diff_id: Option<u64>) -> String {
+    if let Some(name) = difficulty["name"].as_str().filter(|name| !name.is_empty()) {
+        return name.to_string();
+    }
+
+    let catalog = match get_difficulty_catalog().await {
+        Ok(catalog) => catalog,
+        Err(_) => return "Unknown".to_string(),
+    };
+
+    difficulty["id"]
+        .as_u64()
+        .or(diff_id)
+        .and_then(|id| catalog.get(&id))
+        .or_else(|| {
+            let icon = difficulty["icon"].as_str()?;
+            catalog.values().find(|known| known.icon == icon)
+        })
+        .map(|known| known.name.clone())
+        .unwrap_or_else(|| "Unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DifficultyRange, DifficultyTier};
+
+    #[test]
+    fn parses_tier_names() {
+        assert_eq!("P1".parse(), Ok(DifficultyTier::P(1)));
+        assert_eq!("g13".parse(), Ok(DifficultyTier::G(13)));
+        assert_eq!(" U20 ".parse(), Ok(DifficultyTier::U(20)));
+        assert_eq!("QQ".parse(), Ok(DifficultyTier::Qq));
+        assert_eq!(
+            "marathon".parse(),
+            Ok(DifficultyTier::Special("Marathon".to_string()))
+        );
+        assert!("U21".parse::<DifficultyTier>().is_err());
+        assert!("P0".parse::<DifficultyTier>().is_err());
+        assert!("X5".parse::<DifficultyTier>().is_err());
+        assert!("".parse::<DifficultyTier>().is_err());
+    }
+
+    #[test]
+    fn tiers_are_ordered() {
+        assert!(DifficultyTier::P(20) < DifficultyTier::G(1));
+        assert!(DifficultyTier::G(20) < DifficultyTier::U(1));
+        assert!(DifficultyTier::U(2) < DifficultyTier::U(10));
+        assert!(DifficultyTier::U(20) < DifficultyTier::Qq);
+        assert_eq!(DifficultyTier::P(1).order(), Some(0));
+        assert_eq!(DifficultyTier::U(20).order(), Some(59));
+        assert_eq!(DifficultyTier::Special("Gimmick".to_string()).order(), None);
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for name in ["P1", "G13", "U20", "Qq", "Marathon"] {
+            assert_eq!(name.parse::<DifficultyTier>().unwrap().to_string(), name);
+        }
+    }
+
+    #[test]
+    fn ranges() {
+        let range = "u5-g1".parse::<DifficultyRange>().unwrap();
+        assert_eq!(range.min, DifficultyTier::G(1));
+        assert_eq!(range.max, DifficultyTier::U(5));
+        assert_eq!(range.to_string(), "G1-U5");
+        assert_eq!(range.pgu_param(), "G1,U5");
+
+        assert!(range.contains(&DifficultyTier::G(1)));
+        assert!(range.contains(&DifficultyTier::U(5)));
+        assert!(!range.contains(&DifficultyTier::U(6)));
+        assert!(!range.contains(&DifficultyTier::Special("Gimmick".to_string())));
+
+        assert_eq!("U5".parse::<DifficultyRange>().unwrap().to_string(), "U5");
+        assert!("G1-Marathon".parse::<DifficultyRange>().is_err());
+        assert!(DifficultyRange::ALL_PGU.contains(&DifficultyTier::P(1)));
+        assert!(!DifficultyRange::ALL_PGU.contains(&DifficultyTier::Qq));
+    }
+}
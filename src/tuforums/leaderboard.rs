@@ -1,43 +1,245 @@
+#[derive(Debug, Clone)]
+pub struct LeaderboardEntry {
+    /// Rank by ranked score, whatever the leaderboard is sorted by.
+    pub rank: u64,
+    pub player_id: u64,
+    pub name: String,
+    pub ranked_score: f64,
+    pub general_score: f64,
+    pub avg_x_acc: f64,
+    pub passes: u64,
+    pub worlds_firsts: u64,
+    pub top_diff: Option<String>,
+    pub country: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LeaderboardSort {
+    Ranked,
+    General,
+    XAcc,
+    Passes,
+    WorldsFirsts,
+}
+
+impl LeaderboardSort {
+    pub const ALL: [LeaderboardSort; 5] = [
+        LeaderboardSort::Ranked,
+        LeaderboardSort::General,
+        LeaderboardSort::XAcc,
+        LeaderboardSort::Passes,
+        LeaderboardSort::WorldsFirsts,
+    ];
+
+    /// Short id used in command choices and button ids.
+    pub fn id(&self) -> &'static str {
+        match self {
+            LeaderboardSort::Ranked => "ranked",
+            LeaderboardSort::General => "general",
+            LeaderboardSort::XAcc => "xacc",
+            LeaderboardSort::Passes => "passes",
+            LeaderboardSort::WorldsFirsts => "wfs",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<LeaderboardSort> {
+        LeaderboardSort::ALL
+            .into_iter()
+            .find(|sort| sort.id() == id)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LeaderboardSort::Ranked => "Ranked score",
+            LeaderboardSort::General => "General score",
+            LeaderboardSort::XAcc => "X-Accuracy",
+            LeaderboardSort::Passes => "Passes",
+            LeaderboardSort::WorldsFirsts => "World's firsts",
+        }
+    }
+
+    fn api_field(&self) -> &'static str {
+        match self {
+            LeaderboardSort::Ranked => "rankedScore",
+            LeaderboardSort::General => "generalScore",
+            LeaderboardSort::XAcc => "averageXacc",
+            LeaderboardSort::Passes => "totalPasses",
+            LeaderboardSort::WorldsFirsts => "worldsFirstCount",
+        }
+    }
+}
+
+pub struct LeaderboardPage {
+    pub entries: Vec<LeaderboardEntry>,
+    /// Players on the whole leaderboard, not just this page.
+    pub total: u64,
+}
+
 pub async fn get_leaderboard(
     offset: u32,
     length: usize,
-) -> Result<Vec<(u64, String, f64, f64, u64)>, Box<dyn std::error::Error + Send + Sync>> {
+    sort: LeaderboardSort,
+    descending: bool,
+) -> Result<LeaderboardPage, Box<dyn std::error::Error + Send + Sync>> {
     let response = reqwest::get(format!(
-        "https://api.tuforums.com/v2/database/leaderboard?query=&sortBy=rankedScore&order=desc&offset={}&limit={}&showBanned=hide",
-        offset, length
+        "https://api.tuforums.com/v2/database/leaderboard?query=&sortBy={}&order={}&offset={}&limit={}&showBanned=hide",
+        sort.api_field(),
+        if descending { "desc" } else { "asc" },
+        offset,
+        length
     ))
-    .await
-    .expect("Failed to send request");
+    .await?;
 
     if !response.status().is_success() {
         return Err(format!("Request failed with status: {}", response.status()).into());
     }
 
-    let json = response
-        .json::<serde_json::Value>()
-        .await
-        .expect("Failed to parse JSON");
+    let json = response.json::<serde_json::Value>().await?;
 
-    let mut leaderboard = Vec::new();
-
-    let results = json["results"]
+    let entries = json["results"]
         .as_array()
         .ok_or("Missing or invalid 'results' field in JSON")?
         .iter()
         .take(length)
-        .cloned()
+        .map(|entry| {
+            let player = &entry["player"];
+            LeaderboardEntry {
+                rank: entry["rankedScoreRank"].as_u64().unwrap_or(0),
+                player_id: player["id"].as_u64().unwrap_or(0),
+                name: player["name"].as_str().unwrap_or("Unknown").to_string(),
+                ranked_score: entry["rankedScore"].as_f64().unwrap_or(0.0),
+                general_score: entry["generalScore"].as_f64().unwrap_or(0.0),
+                avg_x_acc: entry["averageXacc"].as_f64().unwrap_or(0.0) * 100.0,
+                passes: entry["totalPasses"].as_u64().unwrap_or(0),
+                worlds_firsts: entry["worldsFirstCount"].as_u64().unwrap_or(0),
+                top_diff: entry["topDiff"]["name"]
+                    .as_str()
+                    .map(|name| name.to_string()),
+                country: player["country"]
+                    .as_str()
+                    .filter(|country| !country.is_empty())
+                    .map(|country| country.to_string()),
+            }
+        })
         .collect::<Vec<_>>();
 
-    for entry in results {
-        let ranked_score = entry["rankedScore"].as_f64().unwrap_or(0.0);
-        let avg_accuracy = entry["averageXacc"].as_f64().unwrap_or(0.0) * 100.0;
-        let position = entry["rankedScoreRank"].as_u64().unwrap_or(0);
-        let player = &entry["player"];
-        // let playeruser = &player["user"];
-        let username = player["name"].as_str().unwrap_or("Unknown").to_string();
-        let id = player["id"].as_u64().unwrap_or(0);
-        leaderboard.push((position, username, ranked_score, avg_accuracy, id));
+    let total = json["total"]
+        .as_u64()
+        .or_else(|| json["count"].as_u64())
+        .unwrap_or(offset as u64 + entries.len() as u64);
+
+    Ok(LeaderboardPage { entries, total })
+}
+
+#[derive(Clone)]
+pub struct PlayerSearchResult {
+    pub id: u64,
+    pub name: String,
+    pub rank: u64,
+}
+
+pub async fn search_players(
+    query: &str,
+    limit: u32,
+) -> Result<Vec<PlayerSearchResult>, Box<dyn std::error::Error + Send + Sync>> {
+    let response = reqwest::Client::new()
+        .get("https://api.tuforums.com/v2/database/leaderboard")
+        .query(&[
+            ("query", query),
+            ("sortBy", "rankedScore"),
+            ("order", "desc"),
+            ("offset", "0"),
+            ("limit", &limit.to_string()),
+            ("showBanned", "hide"),
+        ])
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(format!("Request failed with status: {}", response.status()).into());
     }
 
-    Ok(leaderboard)
+    let json: serde_json::Value = response.json().await?;
+
+    let players = json["results"]
+        .as_array()
+        .ok_or("Missing or invalid 'results' field in JSON")?
+        .iter()
+        .filter_map(|entry| {
+            Some(PlayerSearchResult {
+                id: entry["player"]["id"].as_u64()?,
+                name: entry["player"]["name"]
+                    .as_str()
+                    .unwrap_or("Unknown")
+                    .to_string(),
+                rank: entry["rankedScoreRank"].as_u64().unwrap_or(0),
+            })
+        })
+        .collect();
+
+    Ok(players)
+}
+
+/// Estimates the rank a player with `ranked_score` would have, by binary searching the
+/// ranked leaderboard a page at a time. Ranked players can only climb, so the search stops
+/// at the page of their `current_rank`; unranked players (rank 0) search the whole board.
+pub async fn estimate_rank(
+    ranked_score: f64,
+    current_rank: u64,
+) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    const WINDOW: u32 = 100;
+
+    let page = |index: u32| async move {
+        get_leaderboard(
+            index * WINDOW,
+            WINDOW as usize,
+            LeaderboardSort::Ranked,
+            true,
+        )
+        .await
+    };
+    let count_above = |leaders: &[LeaderboardEntry]| {
+        leaders
+            .iter()
+            .filter(|entry| entry.ranked_score > ranked_score)
+            .count() as u32
+    };
+    // Every player on the page stays ahead, so the rank is on a later page.
+    let all_ahead = |leaders: &[LeaderboardEntry]| {
+        !leaders.is_empty() && count_above(leaders) == leaders.len() as u32
+    };
+
+    let first = page(0).await?;
+    if !all_ahead(&first.entries) {
+        return Ok(count_above(&first.entries) as u64 + 1);
+    }
+
+    let pages = first.total.div_ceil(WINDOW as u64) as u32;
+    let mut high = match current_rank {
+        0 => pages,
+        rank => ((rank - 1) / WINDOW as u64) as u32 + 1,
+    }
+    .min(pages)
+    .max(1);
+
+    // Pages before `low` are all ahead, the rank lands on the first page that isn't.
+    let mut low = 1;
+    let mut landing = None;
+    while low < high {
+        let mid = (low + high) / 2;
+        let leaders = page(mid).await?.entries;
+        if all_ahead(&leaders) {
+            low = mid + 1;
+        } else {
+            high = mid;
+            landing = Some((mid, leaders));
+        }
+    }
+
+    let leaders = match landing {
+        Some((index, leaders)) if index == low => leaders,
+        _ => page(low).await?.entries,
+    };
+
+    Ok((low * WINDOW + count_above(&leaders) + 1) as u64)
 }
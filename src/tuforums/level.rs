@@ -1,4 +1,6 @@
-use super::difficulty::{Difficulty, convert_from_hex_to_rgb};
+use super::difficulty::{
+    Difficulty, DifficultyRange, SPECIAL_TIERS, convert_from_hex_to_rgb, difficulty_name,
+};
 
 pub struct Level {
     pub id: u32,
@@ -12,14 +14,22 @@ pub struct Level {
     pub first_clear: String,
     pub dl_link: String,
     pub vido_link: String,
+    pub tile_count: Option<u32>,
+}
+
+#[derive(Clone)]
+pub struct LevelSearchResult {
+    pub id: u32,
+    pub title: String,
+    pub artist: String,
+    pub difficulty: String,
 }
 
 pub async fn get_level(id: u32) -> Result<Level, Box<dyn std::error::Error + Sync + Send>> {
-    let resposne = reqwest::get(format!("https://api.tuforums.com/v2/database/levels/{id}"))
-        .await
-        .expect("Failed to send request");
+    let resposne =
+        reqwest::get(format!("https://api.tuforums.com/v2/database/levels/{id}")).await?;
 
-    let json: serde_json::Value = resposne.json().await.expect("Failed to parse JSON");
+    let json: serde_json::Value = resposne.json().await?;
 
     if !json["error"].is_null() {
         return Err("Level not found".into());
@@ -33,21 +43,30 @@ pub async fn get_level(id: u32) -> Result<Level, Box<dyn std::error::Error + Syn
         id,
         title: map["song"]
             .as_str()
-            .expect("Failed to get song value from JSON")
+            .ok_or("Failed to get song value from JSON")?
             .to_string(),
         artist: map["artist"]
             .as_str()
-            .expect("Failed to get artist value from JSON")
+            .ok_or("Failed to get artist value from JSON")?
             .to_string(),
         creator: map["creator"]
             .as_str()
-            .expect("Failed to get song value from JSON")
+            .ok_or("Failed to get creator value from JSON")?
             .to_string(),
         difficulty: Difficulty {
-            name: "".to_string(),
-            icon: diff["icon"].as_str().unwrap().to_string(),
-            color: convert_from_hex_to_rgb(diff["color"].as_str().unwrap()),
-            score_base: diff["baseScore"].as_f64().unwrap(),
+            name: difficulty_name(diff, map["diffId"].as_u64()).await,
+            icon: diff["icon"]
+                .as_str()
+                .ok_or("Failed to get difficulty icon from JSON")?
+                .to_string(),
+            color: convert_from_hex_to_rgb(
+                diff["color"]
+                    .as_str()
+                    .ok_or("Failed to get difficulty color from JSON")?,
+            ),
+            score_base: diff["baseScore"]
+                .as_f64()
+                .ok_or("Failed to get base score from JSON")?,
         },
         score_base: map["baseScore"].as_f64().unwrap_or(0.),
         clears: map["clears"].as_i64().unwrap_or(0) as u64,
@@ -69,11 +88,120 @@ pub async fn get_level(id: u32) -> Result<Level, Box<dyn std::error::Error + Syn
         },
         dl_link: map["dlLink"].as_str().unwrap_or("unknown").to_string(),
         vido_link: map["videoLink"].as_str().unwrap_or("unknown").to_string(),
+        tile_count: map["tileCount"]
+            .as_u64()
+            .filter(|tile_count| *tile_count > 0)
+            .map(|tile_count| tile_count as u32),
     };
 
     Ok(beatmap)
 }
 
+/// Filters for [`search_levels`]. Empty fields aren't sent to TUF.
+#[derive(Clone, Default)]
+pub struct LevelFilter {
+    pub query: String,
+    pub artist: Option<String>,
+    pub creator: Option<String>,
+    pub difficulty_range: Option<DifficultyRange>,
+    pub cleared: Option<bool>,
+    /// TUF sort order like `RECENT_DESC`, TUF's default when `None`.
+    pub sort: Option<&'static str>,
+}
+
+impl LevelFilter {
+    pub fn query(query: &str) -> Self {
+        LevelFilter {
+            query: query.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// The search text with the artist and creator filters added as TUF query tokens.
+    fn search_text(&self) -> String {
+        let mut tokens = vec![self.query.trim().to_string()];
+        if let Some(artist) = &self.artist {
+            tokens.push(format!("artist:{}", artist));
+        }
+        if let Some(creator) = &self.creator {
+            tokens.push(format!("charter:{}", creator));
+        }
+
+        tokens
+            .into_iter()
+            .filter(|token| !token.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+pub struct LevelSearchPage {
+    pub levels: Vec<LevelSearchResult>,
+    pub total: u64,
+}
+
+pub async fn search_levels(
+    filter: &LevelFilter,
+    offset: u32,
+    limit: u32,
+) -> Result<LevelSearchPage, Box<dyn std::error::Error + Send + Sync>> {
+    let cleared_filter = match filter.cleared {
+        Some(true) => "only",
+        Some(false) => "hide",
+        None => "show",
+    };
+
+    let mut params = vec![
+        ("query", filter.search_text()),
+        ("limit", limit.to_string()),
+        ("offset", offset.to_string()),
+        ("deletedFilter", "hide".to_string()),
+        ("clearedFilter", cleared_filter.to_string()),
+    ];
+    if let Some(range) = &filter.difficulty_range {
+        params.push(("pguRange", range.pgu_param()));
+    }
+    if let Some(sort) = filter.sort {
+        params.push(("sort", sort.to_string()));
+    }
+
+    let response = reqwest::Client::new()
+        .get("https://api.tuforums.com/v2/database/levels")
+        .query(&params)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(format!("Request failed with status: {}", response.status()).into());
+    }
+
+    let json: serde_json::Value = response.json().await?;
+
+    let levels = json["results"]
+        .as_array()
+        .ok_or("Failed to get results from JSON")?
+        .iter()
+        .filter_map(|level| {
+            Some(LevelSearchResult {
+                id: level["id"].as_u64()? as u32,
+                title: level["song"].as_str().unwrap_or("unknown").to_string(),
+                artist: level["artist"].as_str().unwrap_or("unknown").to_string(),
+                difficulty: level["difficulty"]["name"]
+                    .as_str()
+                    .unwrap_or("?")
+                    .to_string(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let total = json["total"]
+        .as_u64()
+        .or_else(|| json["count"].as_u64())
+        .unwrap_or(offset as u64 + levels.len() as u64);
+
+    Ok(LevelSearchPage { levels, total })
+}
+
 #[allow(dead_code)]
 pub async fn get_total_levels() -> Result<u64, Box<dyn std::error::Error + Sync + Send>> {
     let resposne = reqwest::get("https://api.tuforums.com/v2/database/statistics")
@@ -92,8 +220,21 @@ pub async fn get_total_levels() -> Result<u64, Box<dyn std::error::Error + Sync
 }
 
 pub async fn request_random_lvl_id() -> Result<u32, Box<dyn std::error::Error + Send + Sync>> {
-    let response = match reqwest::get("https://api.tuforums.com/v2/database/levels?limit=1&offset=0&query=&sort=RANDOM_ASC&deletedFilter=hide&clearedFilter=show&pguRange=P1,U20&specialDifficulties=Marathon,Gimmick")
-    .await {
+    let response = match reqwest::Client::new()
+        .get("https://api.tuforums.com/v2/database/levels")
+        .query(&[
+            ("limit", "1".to_string()),
+            ("offset", "0".to_string()),
+            ("query", "".to_string()),
+            ("sort", "RANDOM_ASC".to_string()),
+            ("deletedFilter", "hide".to_string()),
+            ("clearedFilter", "show".to_string()),
+            ("pguRange", DifficultyRange::ALL_PGU.pgu_param()),
+            ("specialDifficulties", SPECIAL_TIERS.join(",")),
+        ])
+        .send()
+        .await
+    {
         Ok(res) => res,
         Err(_) => {
             eprintln!("Failed to fetch random level ID");
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use tokio::sync::Mutex;
 
 use crate::cache_manager::{CacheManager, LiveTime};
@@ -25,25 +27,107 @@ pub struct Stats {
 #[derive(Debug, Clone)]
 pub struct Rank(pub i64);
 
+#[derive(Debug, Clone)]
+pub struct Pass {
+    pub id: u64,
+    pub level_id: u32,
+    pub level_title: String,
+    pub level_artist: String,
+    pub difficulty: String,
+    /// Position of the difficulty in TUF's ordering, higher is harder.
+    pub difficulty_order: i64,
+    pub score: f64,
+    pub x_acc: f64,
+    pub speed: f64,
+    pub is_worlds_first: bool,
+    pub uploaded: Option<chrono::DateTime<chrono::FixedOffset>>,
+}
+
+/// The ways `/profile` can list a player's passes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PassView {
+    Top,
+    Recent,
+    WorldsFirsts,
+    ByDifficulty,
+}
+
+impl PassView {
+    pub const ALL: [PassView; 4] = [
+        PassView::Top,
+        PassView::Recent,
+        PassView::WorldsFirsts,
+        PassView::ByDifficulty,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            PassView::Top => "top",
+            PassView::Recent => "recent",
+            PassView::WorldsFirsts => "wf",
+            PassView::ByDifficulty => "diff",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<PassView> {
+        PassView::ALL.into_iter().find(|view| view.id() == id)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PassView::Top => "Top passes",
+            PassView::Recent => "Recent passes",
+            PassView::WorldsFirsts => "World's firsts",
+            PassView::ByDifficulty => "By difficulty",
+        }
+    }
+}
+
+/// The raw `/players/{id}` response, which holds both the profile and the passes.
+/// Cached briefly so a command that needs both only sends one request.
+async fn get_player_json(
+    id: u64,
+    cache_manager: Option<&Mutex<CacheManager>>,
+) -> Result<Arc<serde_json::Value>, Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(cache) = cache_manager
+        && let Some(json) = cache
+            .lock()
+            .await
+            .get::<Arc<serde_json::Value>>(format!("player_{id}").as_str())
+    {
+        return Ok(json.clone());
+    }
+
+    let response =
+        reqwest::get(format!("https://api.tuforums.com/v2/database/players/{id}")).await?;
+
+    let json = Arc::new(response.json::<serde_json::Value>().await?);
+
+    if let Some(cache) = cache_manager {
+        cache.lock().await.add(
+            format!("player_{id}"),
+            json.clone(),
+            Some(LiveTime::Minutes(1)),
+        );
+    }
+
+    Ok(json)
+}
+
 pub async fn get_profile(
     id: u64,
     cache_manager: Option<&Mutex<CacheManager>>,
 ) -> Result<(Profile, bool), Box<dyn std::error::Error + Send + Sync>> {
-    if let Some(cache) = cache_manager {
-        if let Some(profile) = cache
+    if let Some(cache) = cache_manager
+        && let Some(profile) = cache
             .lock()
             .await
             .get::<Profile>(format!("profile_{id}").as_str())
-        {
-            return Ok((profile.clone(), true)); // TODO: come up with a better way without cloning to less use memory
-        }
+    {
+        return Ok((profile.clone(), true)); // TODO: come up with a better way without cloning to less use memory
     }
 
-    let response = reqwest::get(format!("https://api.tuforums.com/v2/database/players/{id}"))
-        .await
-        .expect("Failed to send request");
-
-    let json: serde_json::Value = response.json().await.expect("Failed to parse JSON");
+    let json = get_player_json(id, cache_manager).await?;
 
     let name = json["name"].as_str().unwrap_or("Unknown").to_string();
 
@@ -96,3 +180,98 @@ pub async fn get_profile(
 
     Ok((profile, false))
 }
+
+pub async fn get_passes(
+    id: u64,
+    cache_manager: Option<&Mutex<CacheManager>>,
+) -> Result<Vec<Pass>, Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(cache) = cache_manager
+        && let Some(passes) = cache
+            .lock()
+            .await
+            .get::<Vec<Pass>>(format!("passes_{id}").as_str())
+    {
+        return Ok(passes.clone());
+    }
+
+    let json = get_player_json(id, cache_manager).await?;
+
+    if !json["error"].is_null() {
+        return Err("Player not found".into());
+    }
+
+    let passes = json["passes"]
+        .as_array()
+        .ok_or("Missing or invalid 'passes' field in JSON")?
+        .iter()
+        .filter(|pass| !pass["isDeleted"].as_bool().unwrap_or(false))
+        .map(|pass| {
+            let level = &pass["level"];
+            Pass {
+                id: pass["id"].as_u64().unwrap_or(0),
+                level_id: pass["levelId"].as_u64().unwrap_or(0) as u32,
+                level_title: level["song"].as_str().unwrap_or("unknown").to_string(),
+                level_artist: level["artist"].as_str().unwrap_or("unknown").to_string(),
+                difficulty: level["difficulty"]["name"]
+                    .as_str()
+                    .unwrap_or("?")
+                    .to_string(),
+                difficulty_order: level["difficulty"]["sortOrder"].as_i64().unwrap_or(0),
+                score: pass["scoreV2"].as_f64().unwrap_or(0.),
+                x_acc: pass["accuracy"]
+                    .as_f64()
+                    .or_else(|| pass["judgements"]["accuracy"].as_f64())
+                    .unwrap_or(0.)
+                    * 100.,
+                speed: pass["speed"]
+                    .as_f64()
+                    .filter(|speed| *speed > 0.)
+                    .unwrap_or(1.),
+                is_worlds_first: pass["isWorldsFirst"].as_bool().unwrap_or(false),
+                uploaded: pass["vidUploadTime"]
+                    .as_str()
+                    .and_then(|date| chrono::DateTime::parse_from_rfc3339(date).ok()),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(cache) = cache_manager {
+        cache.lock().await.add(
+            format!("passes_{id}"),
+            passes.clone(),
+            Some(LiveTime::Minutes(5)),
+        );
+    }
+
+    Ok(passes)
+}
+
+/// A player's passes listed the way `view` asks for, built on the cached [`get_passes`].
+/// Top passes keep only the best pass on each level, like ranked score does.
+pub async fn get_pass_history(
+    id: u64,
+    view: PassView,
+    cache_manager: Option<&Mutex<CacheManager>>,
+) -> Result<Vec<Pass>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut passes = get_passes(id, cache_manager).await?;
+
+    match view {
+        PassView::Top => {
+            passes.sort_by(|a, b| b.score.total_cmp(&a.score));
+            let mut seen = std::collections::HashSet::new();
+            passes.retain(|pass| seen.insert(pass.level_id));
+        }
+        PassView::Recent => passes.sort_by_key(|pass| std::cmp::Reverse(pass.uploaded)),
+        PassView::WorldsFirsts => {
+            passes.retain(|pass| pass.is_worlds_first);
+            passes.sort_by_key(|pass| std::cmp::Reverse(pass.uploaded));
+        }
+        PassView::ByDifficulty => passes.sort_by(|a, b| {
+            b.difficulty_order
+                .cmp(&a.difficulty_order)
+                .then(b.score.total_cmp(&a.score))
+        }),
+    }
+
+    Ok(passes)
+}
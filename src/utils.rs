@@ -1,8 +1,6 @@
 use serenity::all::CommandInteraction;
-use sys_info;
 
 //TODO: find a possible way to make those functions to one as a generic function
-#[allow(dead_code)]
 pub fn get_option_as_f64(interaction: &CommandInteraction, name: &str, default: f64) -> f64 {
     interaction
         .data
@@ -61,7 +59,7 @@ pub fn get_memory_info() -> MemoryInfo {
         }
     }
 
-    let mem = sys_info::mem_info().unwrap_or_else(|_| sys_info::MemInfo {
+    let mem = sys_info::mem_info().unwrap_or(sys_info::MemInfo {
         total: 0,
         free: 0,
         avail: 0,